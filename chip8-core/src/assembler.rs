@@ -0,0 +1,1052 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::cpu::PROGRAM_START;
+use crate::instruction::Instruction;
+
+/// Assembles `source` into a ROM loadable at [`PROGRAM_START`].
+///
+/// Both the classic mnemonics printed by the disassembler (`LD V3, 0x1F`,
+/// `label:`, `NAME EQU 4`) and Octo statements (`v3 := 0x1F`, `: label`,
+/// `:const NAME 4`, `if v0 == 1 then`) are accepted and can be mixed.
+/// `db`/`dw` emit bytes and big-endian words, and `include "file"` pastes
+/// another source file in place. `path` names the file `source` came from;
+/// it shows up in errors and includes are resolved relative to it.
+pub fn assemble(source: &str, path: Option<&Path>) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::default();
+    if let Some(path) = path {
+        assembler.includes.push(canonical(path));
+    }
+    assembler.source(source, path.map(|path| Rc::new(path.to_path_buf())))?;
+    assembler.finish()
+}
+
+/// An assembly error and where in the source it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: Option<PathBuf>,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file {
+            Some(file) => write!(
+                f,
+                "{}:{}:{}: {}",
+                file.display(),
+                self.line,
+                self.column,
+                self.message
+            ),
+            None => write!(
+                f,
+                "line {}, column {}: {}",
+                self.line, self.column, self.message
+            ),
+        }
+    }
+}
+
+impl Error for AsmError {}
+
+#[derive(Debug, Clone)]
+struct Pos {
+    file: Option<Rc<PathBuf>>,
+    line: usize,
+    column: usize,
+}
+
+impl Pos {
+    fn error(&self, message: impl Into<String>) -> AsmError {
+        AsmError {
+            file: self.file.as_ref().map(|file| file.to_path_buf()),
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    pos: Pos,
+}
+
+impl Token {
+    fn is(&self, text: &str) -> bool {
+        self.text.eq_ignore_ascii_case(text)
+    }
+}
+
+// Splits source into tokens. Commas are tokens of their own so classic
+// operand lists can be told apart from Octo's space-separated ones.
+fn tokenize(source: &str, file: &Option<Rc<PathBuf>>) -> Result<Vec<Token>, AsmError> {
+    let mut tokens = Vec::new();
+
+    for (index, line) in source.lines().enumerate() {
+        let chars: Vec<char> = line.chars().collect();
+        let pos = |column: usize| Pos {
+            file: file.clone(),
+            line: index + 1,
+            column: column + 1,
+        };
+
+        let mut i = 0;
+        while i < chars.len() {
+            let start = i;
+            match chars[i] {
+                ';' | '#' => break,
+                c if c.is_whitespace() => i += 1,
+                ',' => {
+                    tokens.push(Token {
+                        text: ",".to_string(),
+                        pos: pos(start),
+                    });
+                    i += 1;
+                }
+                '"' => {
+                    i += 1;
+                    while i < chars.len() && chars[i] != '"' {
+                        i += 1;
+                    }
+                    if i == chars.len() {
+                        return Err(pos(start).error("unterminated string"));
+                    }
+                    i += 1;
+                    tokens.push(Token {
+                        text: chars[start..i].iter().collect(),
+                        pos: pos(start),
+                    });
+                }
+                _ => {
+                    while i < chars.len()
+                        && !chars[i].is_whitespace()
+                        && !",;#\"".contains(chars[i])
+                    {
+                        i += 1;
+                    }
+                    tokens.push(Token {
+                        text: chars[start..i].iter().collect(),
+                        pos: pos(start),
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+        .or_else(|| digits.strip_prefix('$'))
+    {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(binary) = digits
+        .strip_prefix("0b")
+        .or_else(|| digits.strip_prefix("0B"))
+    {
+        i64::from_str_radix(binary, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+fn parse_register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix(['v', 'V'])?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn is_name(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+        && parse_register(text).is_none()
+}
+
+// Include cycles are compared on canonical paths so `a.8o` and `./a.8o` match.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(i64),
+    Name(String),
+}
+
+// An operand, checked against `max` once every label is known.
+#[derive(Debug, Clone)]
+struct Arg {
+    value: Value,
+    max: u16,
+    pos: Pos,
+}
+
+enum Item {
+    Instruction {
+        build: fn(&[u16]) -> Instruction,
+        args: Vec<Arg>,
+    },
+    Data {
+        args: Vec<Arg>,
+    },
+}
+
+struct Cursor<'a> {
+    tokens: &'a [Token],
+    next: usize,
+    end: Pos,
+}
+
+impl<'a> Cursor<'a> {
+    fn done(&self) -> bool {
+        self.next >= self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.next)
+    }
+
+    fn peek_is(&self, text: &str) -> bool {
+        self.peek().is_some_and(|token| token.is(text))
+    }
+
+    fn next(&mut self) -> Result<&'a Token, AsmError> {
+        let token = self
+            .tokens
+            .get(self.next)
+            .ok_or_else(|| self.end.error("unexpected end of file"))?;
+        self.next += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), AsmError> {
+        let token = self.next()?;
+        if !token.is(text) {
+            return Err(token
+                .pos
+                .error(format!("expected `{}`, found `{}`", text, token.text)));
+        }
+        Ok(())
+    }
+
+    // Classic operands are separated by commas; Octo leaves them out.
+    fn comma(&mut self) {
+        if self.peek_is(",") {
+            self.next += 1;
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a Token, AsmError> {
+        let token = self.next()?;
+        if !is_name(&token.text) {
+            return Err(token
+                .pos
+                .error(format!("expected a name, found `{}`", token.text)));
+        }
+        Ok(token)
+    }
+
+    fn register(&mut self) -> Result<Arg, AsmError> {
+        let token = self.next()?;
+        match parse_register(&token.text) {
+            Some(x) => Ok(Arg {
+                value: Value::Number(x.into()),
+                max: 0xF,
+                pos: token.pos.clone(),
+            }),
+            None => Err(token
+                .pos
+                .error(format!("expected a register, found `{}`", token.text))),
+        }
+    }
+
+    fn value(&mut self, max: u16) -> Result<Arg, AsmError> {
+        let token = self.next()?;
+        let value = if let Some(number) = parse_number(&token.text) {
+            Value::Number(number)
+        } else if is_name(&token.text) {
+            Value::Name(token.text.clone())
+        } else {
+            return Err(token.pos.error(format!(
+                "expected a number or a name, found `{}`",
+                token.text
+            )));
+        };
+
+        Ok(Arg {
+            value,
+            max,
+            pos: token.pos.clone(),
+        })
+    }
+
+    fn peek_register(&self) -> bool {
+        self.peek()
+            .is_some_and(|token| parse_register(&token.text).is_some())
+    }
+}
+
+#[derive(Default)]
+struct Assembler {
+    items: Vec<Item>,
+    size: usize,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, i64>,
+    // Files currently being assembled, to catch include cycles.
+    includes: Vec<PathBuf>,
+}
+
+impl Assembler {
+    fn source(&mut self, source: &str, file: Option<Rc<PathBuf>>) -> Result<(), AsmError> {
+        let tokens = tokenize(source, &file)?;
+        // Running out of tokens is reported just past the end of the last line.
+        let end = match source.lines().enumerate().last() {
+            Some((index, line)) => Pos {
+                file,
+                line: index + 1,
+                column: line.chars().count() + 1,
+            },
+            None => Pos {
+                file,
+                line: 1,
+                column: 1,
+            },
+        };
+        let mut cursor = Cursor {
+            tokens: &tokens,
+            next: 0,
+            end,
+        };
+
+        while !cursor.done() {
+            self.statement(&mut cursor)?;
+        }
+        Ok(())
+    }
+
+    fn statement(&mut self, cursor: &mut Cursor) -> Result<(), AsmError> {
+        use Instruction::*;
+
+        let token = cursor.next()?;
+        let word = token.text.to_ascii_lowercase();
+
+        if cursor.peek_is("equ") {
+            cursor.next += 1;
+            return self.constant(token, cursor);
+        }
+        if let Some(name) = token.text.strip_suffix(':') {
+            if is_name(name) {
+                return self.label(name, &token.pos);
+            }
+        }
+
+        match word.as_str() {
+            ":" => {
+                let name = cursor.name()?;
+                self.label(&name.text, &name.pos)?;
+            }
+            ":const" => {
+                let name = cursor.name()?;
+                self.constant(name, cursor)?;
+            }
+            "include" | ":include" => self.include(cursor)?,
+            "db" | "dw" => {
+                let max = if word == "db" { 0xFF } else { 0xFFFF };
+                let mut args = vec![cursor.value(max)?];
+                while cursor.peek_is(",") {
+                    cursor.next += 1;
+                    args.push(cursor.value(max)?);
+                }
+                self.size += args.len() * if word == "db" { 1 } else { 2 };
+                self.items.push(Item::Data { args });
+            }
+
+            "cls" | "clear" => self.emit(|_| Cls, vec![]),
+            "ret" | "return" => self.emit(|_| Ret, vec![]),
+            "scd" | "scroll-down" => {
+                let n = cursor.value(0xF)?;
+                self.emit(|a| ScrollDown(a[0] as u8), vec![n]);
+            }
+            "scu" | "scroll-up" => {
+                let n = cursor.value(0xF)?;
+                self.emit(|a| ScrollUp(a[0] as u8), vec![n]);
+            }
+            "scr" | "scroll-right" => self.emit(|_| ScrollRight, vec![]),
+            "scl" | "scroll-left" => self.emit(|_| ScrollLeft, vec![]),
+            "exit" => self.emit(|_| Exit, vec![]),
+            "low" | "lores" => self.emit(|_| Low, vec![]),
+            "high" | "hires" => self.emit(|_| High, vec![]),
+            "jp" if cursor.peek_is("v0") => {
+                cursor.next += 1;
+                cursor.comma();
+                let nnn = cursor.value(0xFFF)?;
+                self.emit(|a| JumpOffset(a[0]), vec![nnn]);
+            }
+            "jp" | "jump" => {
+                let nnn = cursor.value(0xFFF)?;
+                self.emit(|a| Jump(a[0]), vec![nnn]);
+            }
+            "jump0" => {
+                let nnn = cursor.value(0xFFF)?;
+                self.emit(|a| JumpOffset(a[0]), vec![nnn]);
+            }
+            "call" | ":call" => {
+                let nnn = cursor.value(0xFFF)?;
+                self.emit(|a| Call(a[0]), vec![nnn]);
+            }
+            "se" | "sne" => {
+                let x = cursor.register()?;
+                cursor.comma();
+                let equal = word == "se";
+                if cursor.peek_register() {
+                    let y = cursor.register()?;
+                    let build: fn(&[u16]) -> Instruction = if equal {
+                        |a| SkipEqReg(a[0] as u8, a[1] as u8)
+                    } else {
+                        |a| SkipNeReg(a[0] as u8, a[1] as u8)
+                    };
+                    self.emit(build, vec![x, y]);
+                } else {
+                    let kk = cursor.value(0xFF)?;
+                    let build: fn(&[u16]) -> Instruction = if equal {
+                        |a| SkipEqByte(a[0] as u8, a[1] as u8)
+                    } else {
+                        |a| SkipNeByte(a[0] as u8, a[1] as u8)
+                    };
+                    self.emit(build, vec![x, kk]);
+                }
+            }
+            "save" | "load" => {
+                let x = cursor.register()?;
+                let save = word == "save";
+                if cursor.peek_is(",") || cursor.peek_is("-") {
+                    cursor.next += 1;
+                    let y = cursor.register()?;
+                    let build: fn(&[u16]) -> Instruction = if save {
+                        |a| SaveRange(a[0] as u8, a[1] as u8)
+                    } else {
+                        |a| LoadRange(a[0] as u8, a[1] as u8)
+                    };
+                    self.emit(build, vec![x, y]);
+                } else if save {
+                    self.emit(|a| StoreRegs(a[0] as u8), vec![x]);
+                } else {
+                    self.emit(|a| LoadRegs(a[0] as u8), vec![x]);
+                }
+            }
+            "saveflags" => {
+                let x = cursor.register()?;
+                self.emit(|a| StoreFlags(a[0] as u8), vec![x]);
+            }
+            "loadflags" => {
+                let x = cursor.register()?;
+                self.emit(|a| LoadFlags(a[0] as u8), vec![x]);
+            }
+            "ld" => self.classic_load(cursor)?,
+            "add" if cursor.peek_is("i") => {
+                cursor.next += 1;
+                cursor.comma();
+                let x = cursor.register()?;
+                self.emit(|a| AddIndex(a[0] as u8), vec![x]);
+            }
+            "add" => {
+                let x = cursor.register()?;
+                cursor.comma();
+                if cursor.peek_register() {
+                    let y = cursor.register()?;
+                    self.emit(|a| AddReg(a[0] as u8, a[1] as u8), vec![x, y]);
+                } else {
+                    let kk = cursor.value(0xFF)?;
+                    self.emit(|a| AddByte(a[0] as u8, a[1] as u8), vec![x, kk]);
+                }
+            }
+            "or" | "and" | "xor" | "sub" | "subn" | "shr" | "shl" => {
+                let build: fn(&[u16]) -> Instruction = match word.as_str() {
+                    "or" => |a| Or(a[0] as u8, a[1] as u8),
+                    "and" => |a| And(a[0] as u8, a[1] as u8),
+                    "xor" => |a| Xor(a[0] as u8, a[1] as u8),
+                    "sub" => |a| Sub(a[0] as u8, a[1] as u8),
+                    "subn" => |a| SubReverse(a[0] as u8, a[1] as u8),
+                    "shr" => |a| ShiftRight(a[0] as u8, a[1] as u8),
+                    _ => |a| ShiftLeft(a[0] as u8, a[1] as u8),
+                };
+                let x = cursor.register()?;
+                // `SHR Vx` shifts Vx in place whichever shift quirk is set.
+                let y = if word.starts_with("sh") && !cursor.peek_is(",") {
+                    x.clone()
+                } else {
+                    cursor.comma();
+                    cursor.register()?
+                };
+                self.emit(build, vec![x, y]);
+            }
+            "rnd" => {
+                let x = cursor.register()?;
+                cursor.comma();
+                let kk = cursor.value(0xFF)?;
+                self.emit(|a| Random(a[0] as u8, a[1] as u8), vec![x, kk]);
+            }
+            "drw" | "sprite" => {
+                let x = cursor.register()?;
+                cursor.comma();
+                let y = cursor.register()?;
+                cursor.comma();
+                let n = cursor.value(0xF)?;
+                self.emit(|a| Draw(a[0] as u8, a[1] as u8, a[2] as u8), vec![x, y, n]);
+            }
+            "skp" => {
+                let x = cursor.register()?;
+                self.emit(|a| SkipKey(a[0] as u8), vec![x]);
+            }
+            "sknp" => {
+                let x = cursor.register()?;
+                self.emit(|a| SkipNotKey(a[0] as u8), vec![x]);
+            }
+            "plane" => {
+                let n = cursor.value(0x3)?;
+                self.emit(|a| Plane(a[0] as u8), vec![n]);
+            }
+            "audio" => self.emit(|_| Audio, vec![]),
+            "pitch" => {
+                if cursor.peek_is(":=") {
+                    cursor.next += 1;
+                }
+                let x = cursor.register()?;
+                self.emit(|a| Pitch(a[0] as u8), vec![x]);
+            }
+            "bcd" => {
+                let x = cursor.register()?;
+                self.emit(|a| StoreBcd(a[0] as u8), vec![x]);
+            }
+            "delay" | "buzzer" => {
+                cursor.expect(":=")?;
+                let x = cursor.register()?;
+                if word == "delay" {
+                    self.emit(|a| SetDelay(a[0] as u8), vec![x]);
+                } else {
+                    self.emit(|a| SetSound(a[0] as u8), vec![x]);
+                }
+            }
+            "i" => self.octo_index(cursor)?,
+            "if" => self.octo_if(cursor)?,
+            _ if parse_register(&word).is_some() => {
+                cursor.next -= 1;
+                self.octo_assign(cursor)?;
+            }
+            _ if parse_number(&word).is_some() => {
+                cursor.next -= 1;
+                let byte = cursor.value(0xFF)?;
+                self.size += 1;
+                self.items.push(Item::Data { args: vec![byte] });
+            }
+            // A bare name calls the subroutine, as in Octo.
+            _ if is_name(&word) => {
+                cursor.next -= 1;
+                let nnn = cursor.value(0xFFF)?;
+                self.emit(|a| Call(a[0]), vec![nnn]);
+            }
+            _ => {
+                return Err(token
+                    .pos
+                    .error(format!("unknown instruction `{}`", token.text)))
+            }
+        }
+
+        Ok(())
+    }
+
+    // `LD dst, src` in all of its classic forms.
+    fn classic_load(&mut self, cursor: &mut Cursor) -> Result<(), AsmError> {
+        use Instruction::*;
+
+        let dst = cursor.next()?;
+        cursor.comma();
+
+        if let Some(x) = parse_register(&dst.text) {
+            let x = Arg {
+                value: Value::Number(x.into()),
+                max: 0xF,
+                pos: dst.pos.clone(),
+            };
+            if cursor.peek_register() {
+                let y = cursor.register()?;
+                self.emit(|a| LoadReg(a[0] as u8, a[1] as u8), vec![x, y]);
+                return Ok(());
+            }
+            let build: fn(&[u16]) -> Instruction = match cursor.peek() {
+                Some(src) if src.is("dt") => |a| LoadDelay(a[0] as u8),
+                Some(src) if src.is("k") => |a| WaitKey(a[0] as u8),
+                Some(src) if src.is("[i]") => |a| LoadRegs(a[0] as u8),
+                Some(src) if src.is("r") => |a| LoadFlags(a[0] as u8),
+                _ => {
+                    let kk = cursor.value(0xFF)?;
+                    self.emit(|a| LoadByte(a[0] as u8, a[1] as u8), vec![x, kk]);
+                    return Ok(());
+                }
+            };
+            cursor.next += 1;
+            self.emit(build, vec![x]);
+            return Ok(());
+        }
+
+        if dst.is("i") {
+            if cursor.peek_is("long") {
+                cursor.next += 1;
+                let nnnn = cursor.value(0xFFFF)?;
+                self.emit(|a| LoadLongIndex(a[0]), vec![nnnn]);
+            } else {
+                let nnn = cursor.value(0xFFF)?;
+                self.emit(|a| LoadIndex(a[0]), vec![nnn]);
+            }
+            return Ok(());
+        }
+
+        let build: fn(&[u16]) -> Instruction = match dst.text.to_ascii_lowercase().as_str() {
+            "dt" => |a| SetDelay(a[0] as u8),
+            "st" => |a| SetSound(a[0] as u8),
+            "f" => |a| LoadFont(a[0] as u8),
+            "hf" => |a| LoadBigFont(a[0] as u8),
+            "b" => |a| StoreBcd(a[0] as u8),
+            "[i]" => |a| StoreRegs(a[0] as u8),
+            "r" => |a| StoreFlags(a[0] as u8),
+            _ => return Err(dst.pos.error(format!("cannot load into `{}`", dst.text))),
+        };
+        let x = cursor.register()?;
+        self.emit(build, vec![x]);
+        Ok(())
+    }
+
+    // `vX := ...`, `vX += ...` and the other Octo register operators.
+    fn octo_assign(&mut self, cursor: &mut Cursor) -> Result<(), AsmError> {
+        use Instruction::*;
+
+        let x = cursor.register()?;
+        let op = cursor.next()?;
+
+        if op.is(":=") {
+            if cursor.peek_register() {
+                let y = cursor.register()?;
+                self.emit(|a| LoadReg(a[0] as u8, a[1] as u8), vec![x, y]);
+            } else if cursor.peek_is("random") {
+                cursor.next += 1;
+                let kk = cursor.value(0xFF)?;
+                self.emit(|a| Random(a[0] as u8, a[1] as u8), vec![x, kk]);
+            } else if cursor.peek_is("delay") {
+                cursor.next += 1;
+                self.emit(|a| LoadDelay(a[0] as u8), vec![x]);
+            } else if cursor.peek_is("key") {
+                cursor.next += 1;
+                self.emit(|a| WaitKey(a[0] as u8), vec![x]);
+            } else {
+                let kk = cursor.value(0xFF)?;
+                self.emit(|a| LoadByte(a[0] as u8, a[1] as u8), vec![x, kk]);
+            }
+            return Ok(());
+        }
+
+        if op.is("+=") && !cursor.peek_register() {
+            let kk = cursor.value(0xFF)?;
+            self.emit(|a| AddByte(a[0] as u8, a[1] as u8), vec![x, kk]);
+            return Ok(());
+        }
+
+        let build: fn(&[u16]) -> Instruction = match op.text.as_str() {
+            "+=" => |a| AddReg(a[0] as u8, a[1] as u8),
+            "-=" => |a| Sub(a[0] as u8, a[1] as u8),
+            "=-" => |a| SubReverse(a[0] as u8, a[1] as u8),
+            "|=" => |a| Or(a[0] as u8, a[1] as u8),
+            "&=" => |a| And(a[0] as u8, a[1] as u8),
+            "^=" => |a| Xor(a[0] as u8, a[1] as u8),
+            ">>=" => |a| ShiftRight(a[0] as u8, a[1] as u8),
+            "<<=" => |a| ShiftLeft(a[0] as u8, a[1] as u8),
+            _ => {
+                return Err(op
+                    .pos
+                    .error(format!("unknown register operator `{}`", op.text)))
+            }
+        };
+        let y = cursor.register()?;
+        self.emit(build, vec![x, y]);
+        Ok(())
+    }
+
+    // `i := nnn`, `i := long nnnn`, `i := hex vX`, `i := bighex vX`, `i += vX`.
+    fn octo_index(&mut self, cursor: &mut Cursor) -> Result<(), AsmError> {
+        use Instruction::*;
+
+        let op = cursor.next()?;
+        if op.is("+=") {
+            let x = cursor.register()?;
+            self.emit(|a| AddIndex(a[0] as u8), vec![x]);
+            return Ok(());
+        }
+        if !op.is(":=") {
+            return Err(op
+                .pos
+                .error(format!("expected `:=` or `+=`, found `{}`", op.text)));
+        }
+
+        if cursor.peek_is("long") {
+            cursor.next += 1;
+            let nnnn = cursor.value(0xFFFF)?;
+            self.emit(|a| LoadLongIndex(a[0]), vec![nnnn]);
+        } else if cursor.peek_is("hex") {
+            cursor.next += 1;
+            let x = cursor.register()?;
+            self.emit(|a| LoadFont(a[0] as u8), vec![x]);
+        } else if cursor.peek_is("bighex") {
+            cursor.next += 1;
+            let x = cursor.register()?;
+            self.emit(|a| LoadBigFont(a[0] as u8), vec![x]);
+        } else {
+            let nnn = cursor.value(0xFFF)?;
+            self.emit(|a| LoadIndex(a[0]), vec![nnn]);
+        }
+        Ok(())
+    }
+
+    // `if <condition> then` skips the next statement unless the condition
+    // holds, so it assembles to the skip for the opposite condition.
+    fn octo_if(&mut self, cursor: &mut Cursor) -> Result<(), AsmError> {
+        use Instruction::*;
+
+        let x = cursor.register()?;
+        let op = cursor.next()?;
+        let op_text = op.text.to_ascii_lowercase();
+
+        match op_text.as_str() {
+            "key" => self.emit(|a| SkipNotKey(a[0] as u8), vec![x]),
+            "-key" => self.emit(|a| SkipKey(a[0] as u8), vec![x]),
+            "==" | "!=" => {
+                let equal = op_text == "==";
+                if cursor.peek_register() {
+                    let y = cursor.register()?;
+                    let build: fn(&[u16]) -> Instruction = if equal {
+                        |a| SkipNeReg(a[0] as u8, a[1] as u8)
+                    } else {
+                        |a| SkipEqReg(a[0] as u8, a[1] as u8)
+                    };
+                    self.emit(build, vec![x, y]);
+                } else {
+                    let kk = cursor.value(0xFF)?;
+                    let build: fn(&[u16]) -> Instruction = if equal {
+                        |a| SkipNeByte(a[0] as u8, a[1] as u8)
+                    } else {
+                        |a| SkipEqByte(a[0] as u8, a[1] as u8)
+                    };
+                    self.emit(build, vec![x, kk]);
+                }
+            }
+            _ => return Err(op.pos.error(format!("unsupported condition `{}`", op.text))),
+        }
+
+        cursor.expect("then")
+    }
+
+    fn label(&mut self, name: &str, pos: &Pos) -> Result<(), AsmError> {
+        if self.labels.contains_key(name) || self.constants.contains_key(name) {
+            return Err(pos.error(format!("`{}` is already defined", name)));
+        }
+        self.labels
+            .insert(name.to_string(), (PROGRAM_START + self.size) as u16);
+        Ok(())
+    }
+
+    // Constants must be given a number or an earlier constant.
+    fn constant(&mut self, name: &Token, cursor: &mut Cursor) -> Result<(), AsmError> {
+        if self.labels.contains_key(&name.text) || self.constants.contains_key(&name.text) {
+            return Err(name
+                .pos
+                .error(format!("`{}` is already defined", name.text)));
+        }
+
+        let token = cursor.next()?;
+        let value = match parse_number(&token.text) {
+            Some(value) => value,
+            None => match self.constants.get(&token.text) {
+                Some(&value) => value,
+                None => {
+                    return Err(token.pos.error(format!(
+                        "expected a number or an earlier constant, found `{}`",
+                        token.text
+                    )))
+                }
+            },
+        };
+
+        self.constants.insert(name.text.clone(), value);
+        Ok(())
+    }
+
+    fn include(&mut self, cursor: &mut Cursor) -> Result<(), AsmError> {
+        let token = cursor.next()?;
+        let Some(relative) = token
+            .text
+            .strip_prefix('"')
+            .and_then(|text| text.strip_suffix('"'))
+        else {
+            return Err(token
+                .pos
+                .error(format!("expected a quoted path, found `{}`", token.text)));
+        };
+
+        let path = match self.includes.last().and_then(|file| file.parent()) {
+            Some(dir) => dir.join(relative),
+            None => PathBuf::from(relative),
+        };
+        if self.includes.contains(&canonical(&path)) {
+            return Err(token
+                .pos
+                .error(format!("{} includes itself", path.display())));
+        }
+        let source = fs::read_to_string(&path).map_err(|err| {
+            token
+                .pos
+                .error(format!("could not read {}: {}", path.display(), err))
+        })?;
+
+        self.includes.push(canonical(&path));
+        self.source(&source, Some(Rc::new(path)))?;
+        self.includes.pop();
+        Ok(())
+    }
+
+    fn emit(&mut self, build: fn(&[u16]) -> Instruction, args: Vec<Arg>) {
+        self.size += build(&vec![0; args.len()]).size();
+        self.items.push(Item::Instruction { build, args });
+    }
+
+    fn resolve(&self, arg: &Arg) -> Result<u16, AsmError> {
+        let value = match &arg.value {
+            Value::Number(value) => *value,
+            Value::Name(name) => match (self.constants.get(name), self.labels.get(name)) {
+                (Some(&value), _) => value,
+                (None, Some(&addr)) => addr.into(),
+                (None, None) => return Err(arg.pos.error(format!("`{}` is not defined", name))),
+            },
+        };
+
+        // Negative bytes are allowed and stored as two's complement.
+        if arg.max == 0xFF && (-0x80..0).contains(&value) {
+            return Ok((value & 0xFF) as u16);
+        }
+        if !(0..=i64::from(arg.max)).contains(&value) {
+            return Err(arg.pos.error(format!(
+                "{} does not fit, the limit is {:#X}",
+                value, arg.max
+            )));
+        }
+        Ok(value as u16)
+    }
+
+    fn finish(self) -> Result<Vec<u8>, AsmError> {
+        let mut rom = Vec::with_capacity(self.size);
+
+        for item in &self.items {
+            match item {
+                Item::Instruction { build, args } => {
+                    let values = args
+                        .iter()
+                        .map(|arg| self.resolve(arg))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    rom.extend(build(&values).encode());
+                }
+                Item::Data { args } => {
+                    for arg in args {
+                        let value = self.resolve(arg)?;
+                        if arg.max == 0xFF {
+                            rom.push(value as u8);
+                        } else {
+                            rom.extend(value.to_be_bytes());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(rom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disassemble;
+    use crate::platform::Platform;
+
+    fn words(words: &[u16]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_be_bytes()).collect()
+    }
+
+    fn error(source: &str) -> AsmError {
+        assemble(source, None).unwrap_err()
+    }
+
+    #[test]
+    fn control_flow() {
+        let source =
+            "CLS\nRET\nSCD 3\nSCU 2\nSCR\nSCL\nEXIT\nLOW\nHIGH\nclear\nreturn\nlores\nhires";
+        let expected = [
+            0x00E0, 0x00EE, 0x00C3, 0x00D2, 0x00FB, 0x00FC, 0x00FD, 0x00FE, 0x00FF, 0x00E0, 0x00EE,
+            0x00FE, 0x00FF,
+        ];
+        assert_eq!(assemble(source, None), Ok(words(&expected)));
+    }
+
+    #[test]
+    fn jumps_and_calls() {
+        let source = "start:\nJP start\nCALL sub\nJP V0, 0x300\nsub:\njump0 0x300\n: end\nend";
+        let expected = [0x1200, 0x2206, 0xB300, 0xB300, 0x2208];
+        assert_eq!(assemble(source, None), Ok(words(&expected)));
+    }
+
+    #[test]
+    fn skips() {
+        let source = "SE V1, 2\nSNE V1, V2\nSE V1, V2\nSNE V3, 0x10\nSKP V4\nSKNP V5\n\
+                      if v1 == 2 then\nif v1 != v2 then\nif v4 key then\nif v5 -key then";
+        let expected = [
+            0x3102, 0x9120, 0x5120, 0x4310, 0xE49E, 0xE5A1, 0x4102, 0x5120, 0xE4A1, 0xE59E,
+        ];
+        assert_eq!(assemble(source, None), Ok(words(&expected)));
+    }
+
+    #[test]
+    fn register_arithmetic() {
+        let source = "LD V0, 0x12\nADD V0, 1\nLD V1, V0\nOR V1, V2\nAND V1, V2\nXOR V1, V2\n\
+                      ADD V1, V2\nSUB V1, V2\nSHR V1\nSUBN V1, V2\nSHL V1, V2\nRND V3, 0xF0\n\
+                      v0 := 0x12\nv0 += -1\nv1 := v0\nv1 -= v2\nv1 =- v2\nv1 >>= v1\n\
+                      v3 := random 0xF0";
+        let expected = [
+            0x6012, 0x7001, 0x8100, 0x8121, 0x8122, 0x8123, 0x8124, 0x8125, 0x8116, 0x8127, 0x812E,
+            0xC3F0, 0x6012, 0x70FF, 0x8100, 0x8125, 0x8127, 0x8116, 0xC3F0,
+        ];
+        assert_eq!(assemble(source, None), Ok(words(&expected)));
+    }
+
+    #[test]
+    fn index_and_memory() {
+        let source = "LD I, 0x300\nLD I, LONG 0x1234\nADD I, V2\nLD F, V3\nLD HF, V4\nLD B, V5\n\
+                      LD [I], V6\nLD V7, [I]\nLD R, V8\nLD V9, R\nSAVE V1, V3\nLOAD V1, V3\n\
+                      i := 0x300\ni := long 0x1234\ni += v2\ni := hex v3\ni := bighex v4\n\
+                      bcd v5\nsave v6\nload v7\nsaveflags v8\nloadflags v9\nsave v1 - v3";
+        let expected = [
+            0xA300, 0xF000, 0x1234, 0xF21E, 0xF329, 0xF430, 0xF533, 0xF655, 0xF765, 0xF875, 0xF985,
+            0x5132, 0x5133, 0xA300, 0xF000, 0x1234, 0xF21E, 0xF329, 0xF430, 0xF533, 0xF655, 0xF765,
+            0xF875, 0xF985, 0x5132,
+        ];
+        assert_eq!(assemble(source, None), Ok(words(&expected)));
+    }
+
+    #[test]
+    fn timers_keys_and_drawing() {
+        let source = "LD V1, DT\nLD V2, K\nLD DT, V3\nLD ST, V4\nDRW V0, V1, 5\nPLANE 3\nAUDIO\n\
+                      PITCH V6\nv1 := delay\nv2 := key\ndelay := v3\nbuzzer := v4\n\
+                      sprite v0 v1 5\nplane 3\naudio\npitch := v6";
+        let expected = [
+            0xF107, 0xF20A, 0xF315, 0xF418, 0xD015, 0xF301, 0xF002, 0xF63A, 0xF107, 0xF20A, 0xF315,
+            0xF418, 0xD015, 0xF301, 0xF002, 0xF63A,
+        ];
+        assert_eq!(assemble(source, None), Ok(words(&expected)));
+    }
+
+    #[test]
+    fn data_and_constants() {
+        let source =
+            "COUNT EQU 3\n:const ALIAS COUNT\ndb 1, ALIAS, -1\ndw 0x1234, here\nhere:\n0x42";
+        let expected = vec![0x01, 0x03, 0xFF, 0x12, 0x34, 0x02, 0x07, 0x42];
+        assert_eq!(assemble(source, None), Ok(expected));
+    }
+
+    #[test]
+    fn disassembly_assembles_to_the_same_rom() {
+        let rom = words(&[
+            0x00E0, 0x6A05, 0xA214, 0xF000, 0x1234, 0x220E, 0x120C, 0x3A05, 0x8AB4, 0x00EE, 0xFF81,
+            0x81FF,
+        ]);
+        let listing = disassemble(&rom, Platform::XoChip);
+        let mut source = String::new();
+        for line in &listing.lines {
+            if let Some(label) = &line.label {
+                source += &format!("{}:\n", label);
+            }
+            match &line.instruction {
+                Some(instruction) => source += &format!("{}\n", instruction),
+                None => {
+                    let bytes: Vec<String> = line
+                        .bytes
+                        .iter()
+                        .map(|byte| format!("{:#04X}", byte))
+                        .collect();
+                    source += &format!("DB {}\n", bytes.join(", "));
+                }
+            }
+        }
+        assert_eq!(assemble(&source, None), Ok(rom));
+    }
+
+    #[test]
+    fn operand_out_of_range() {
+        let err = error("CLS\nLD V0, 256");
+        assert_eq!((err.line, err.column), (2, 8));
+        assert_eq!(err.message, "256 does not fit, the limit is 0xFF");
+    }
+
+    #[test]
+    fn undefined_label() {
+        let err = error("JP nowhere");
+        assert_eq!((err.line, err.column), (1, 4));
+        assert_eq!(err.message, "`nowhere` is not defined");
+    }
+
+    #[test]
+    fn end_of_file_is_on_the_last_line() {
+        let err = error("LD V0,");
+        assert_eq!((err.line, err.column), (1, 7));
+        assert_eq!(err.message, "unexpected end of file");
+
+        let err = error("CLS\nLD V0,\n");
+        assert_eq!((err.line, err.column), (2, 7));
+    }
+
+    #[test]
+    fn include_cycle() {
+        let dir = std::env::temp_dir().join(format!("chip8-asm-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let a = dir.join("a.8o");
+        let b = dir.join("b.8o");
+        fs::write(&a, "CLS\ninclude \"b.8o\"\n").unwrap();
+        fs::write(&b, "RET\ninclude \"a.8o\"\n").unwrap();
+
+        let err = assemble(&fs::read_to_string(&a).unwrap(), Some(&a)).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(err.file.as_deref().and_then(Path::file_name), b.file_name());
+        assert_eq!((err.line, err.column), (2, 9));
+        assert!(err.message.ends_with("a.8o includes itself"));
+    }
+}
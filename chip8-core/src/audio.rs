@@ -0,0 +1,243 @@
+use std::io::{self, Write};
+
+use crate::cpu::Chip8;
+use crate::timers::TIMER_HZ;
+
+pub const SAMPLE_RATE: u32 = 44_100;
+pub const DEFAULT_PITCH_HZ: f32 = 440.0;
+pub const DEFAULT_VOLUME: f32 = 0.25;
+/// XO-CHIP pitch register value that plays a pattern at 4000 bits per second.
+pub const DEFAULT_PITCH: u8 = 64;
+
+/// The rate, in bits per second, at which XO-CHIP plays its audio pattern for `pitch`.
+pub fn pattern_rate(pitch: u8) -> f32 {
+    4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0)
+}
+
+/// How the beeper sounds while `sound_timer` is non-zero.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BeeperConfig {
+    pub pitch: f32,
+    pub volume: f32,
+    pub muted: bool,
+}
+
+impl Default for BeeperConfig {
+    fn default() -> Self {
+        Self {
+            pitch: DEFAULT_PITCH_HZ,
+            volume: DEFAULT_VOLUME,
+            muted: false,
+        }
+    }
+}
+
+/// An endless square wave, as mono `f32` samples between `-volume` and `volume`.
+#[derive(Debug, Clone)]
+pub struct SquareWave {
+    pitch: f32,
+    volume: f32,
+    sample_rate: u32,
+    // Position within the current period, in 0.0..1.0.
+    phase: f32,
+}
+
+impl SquareWave {
+    pub fn new(pitch: f32, volume: f32, sample_rate: u32) -> Self {
+        Self {
+            pitch,
+            volume,
+            sample_rate,
+            phase: 0.0,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+impl Iterator for SquareWave {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = if self.phase < 0.5 {
+            self.volume
+        } else {
+            -self.volume
+        };
+
+        self.phase = (self.phase + self.pitch / self.sample_rate as f32).fract();
+        Some(sample)
+    }
+}
+
+/// Loops a 128-bit XO-CHIP audio pattern, one bit per step, as `-volume` or `volume`.
+#[derive(Debug, Clone)]
+pub struct PatternWave {
+    pattern: [u8; 16],
+    rate: f32,
+    volume: f32,
+    sample_rate: u32,
+    // Position in the pattern, in bits.
+    position: f32,
+}
+
+impl PatternWave {
+    pub fn new(pattern: [u8; 16], pitch: u8, volume: f32, sample_rate: u32) -> Self {
+        Self {
+            pattern,
+            rate: pattern_rate(pitch),
+            volume,
+            sample_rate,
+            position: 0.0,
+        }
+    }
+
+    /// Switches to a new pattern or pitch without restarting playback.
+    pub fn set(&mut self, pattern: [u8; 16], pitch: u8) {
+        self.pattern = pattern;
+        self.rate = pattern_rate(pitch);
+    }
+}
+
+impl Iterator for PatternWave {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let bit = self.position as usize;
+        let lit = self.pattern[bit / 8] & (0x80 >> (bit % 8)) != 0;
+
+        self.position = (self.position + self.rate / self.sample_rate as f32) % 128.0;
+        Some(if lit { self.volume } else { -self.volume })
+    }
+}
+
+/// Everything the speaker plays: silence, the beeper tone, or the XO-CHIP
+/// audio pattern once a ROM has loaded one. Call [`Speaker::update`] once a
+/// frame and pull samples from it as an iterator.
+#[derive(Debug, Clone)]
+pub struct Speaker {
+    pub config: BeeperConfig,
+    square: SquareWave,
+    pattern: Option<PatternWave>,
+    playing: bool,
+}
+
+impl Speaker {
+    pub fn new(config: BeeperConfig) -> Self {
+        Self {
+            config,
+            square: SquareWave::new(config.pitch, config.volume, SAMPLE_RATE),
+            pattern: None,
+            playing: false,
+        }
+    }
+
+    /// Follows the machine's sound timer and audio pattern.
+    pub fn update(&mut self, chip8: &Chip8) {
+        self.playing = chip8.sound_timer > 0 && !self.config.muted;
+
+        match (&mut self.pattern, chip8.audio_pattern) {
+            (Some(wave), Some(pattern)) => wave.set(pattern, chip8.pitch),
+            (None, Some(pattern)) => {
+                self.pattern = Some(PatternWave::new(
+                    pattern,
+                    chip8.pitch,
+                    self.config.volume,
+                    SAMPLE_RATE,
+                ))
+            }
+            (_, None) => self.pattern = None,
+        }
+    }
+
+    /// Silences the speaker until the next [`Speaker::update`].
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+}
+
+impl Iterator for Speaker {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if !self.playing {
+            return Some(0.0);
+        }
+
+        match &mut self.pattern {
+            Some(wave) => wave.next(),
+            None => self.square.next(),
+        }
+    }
+}
+
+/// Records what the beeper would play, one 60Hz frame at a time, so headless
+/// runs can write the tone to a WAV file.
+#[derive(Debug, Clone)]
+pub struct ToneRecorder {
+    speaker: Speaker,
+    samples: Vec<i16>,
+}
+
+impl ToneRecorder {
+    pub fn new(config: BeeperConfig) -> Self {
+        Self {
+            speaker: Speaker::new(config),
+            samples: Vec::new(),
+        }
+    }
+
+    /// Appends one frame of whatever the speaker plays for the machine's current state.
+    pub fn record_frame(&mut self, chip8: &Chip8) {
+        self.speaker.update(chip8);
+        for _ in 0..SAMPLE_RATE / TIMER_HZ {
+            let sample = self.speaker.next().unwrap_or(0.0);
+            self.samples.push((sample * i16::MAX as f32) as i16);
+        }
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.samples
+    }
+
+    pub fn write_wav<W: Write>(&self, writer: W) -> io::Result<()> {
+        write_wav(writer, &self.samples, SAMPLE_RATE)
+    }
+}
+
+/// Writes mono 16-bit PCM samples as a WAV file.
+pub fn write_wav<W: Write>(mut writer: W, samples: &[i16], sample_rate: u32) -> io::Result<()> {
+    const CHANNELS: u16 = 1;
+    const BITS_PER_SAMPLE: u16 = 16;
+
+    let block_align = CHANNELS * BITS_PER_SAMPLE / 8;
+    let byte_rate = sample_rate * block_align as u32;
+    let data_len = (samples.len() * block_align as usize) as u32;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + data_len).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?; // PCM
+    writer.write_all(&CHANNELS.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&byte_rate.to_le_bytes())?;
+    writer.write_all(&block_align.to_le_bytes())?;
+    writer.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        writer.write_all(&sample.to_le_bytes())?;
+    }
+
+    writer.flush()
+}
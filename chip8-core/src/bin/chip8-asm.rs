@@ -0,0 +1,57 @@
+use chip8_core::assemble;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process;
+
+const USAGE: &str = "Usage: chip8-asm <source> [-o out.ch8]";
+
+fn main() {
+    let mut source_path = None;
+    let mut out_path = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(path) => out_path = Some(PathBuf::from(path)),
+                None => exit_with_usage(),
+            },
+            _ if source_path.is_none() => source_path = Some(PathBuf::from(arg)),
+            _ => exit_with_usage(),
+        }
+    }
+
+    let Some(source_path) = source_path else {
+        exit_with_usage();
+    };
+    let out_path = out_path.unwrap_or_else(|| source_path.with_extension("ch8"));
+
+    let source = match fs::read_to_string(&source_path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("Could not read {}: {}", source_path.display(), err);
+            process::exit(1);
+        }
+    };
+
+    let rom = match assemble(&source, Some(&source_path)) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+    };
+
+    if let Err(err) = fs::write(&out_path, &rom) {
+        eprintln!("Could not write {}: {}", out_path.display(), err);
+        process::exit(1);
+    }
+    println!("Wrote {} bytes to {}", rom.len(), out_path.display());
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
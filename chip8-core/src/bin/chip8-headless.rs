@@ -0,0 +1,243 @@
+use chip8_core::{
+    sha1, BeeperConfig, Chip8, Chip8Error, Clock, Movie, Platform, Quirks, RandomKind,
+    ToneRecorder, DEFAULT_CPU_HZ,
+};
+
+use std::env;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::process;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Timers tick and frames are counted at 60Hz.
+const FRAME_HZ: u64 = 60;
+const NANOS_PER_SEC: u64 = 1_000_000_000;
+
+const USAGE: &str = "Usage: chip8-headless <rom> [--cycles N | --frames N] [--hz N] \
+                     [--platform chip8|schip|xochip] \
+                     [--quirks vip|chip48|schip|xochip|modern|amiga] [--wav FILE] [--pitch HZ] \
+                     [--volume V] [--mute] [--seed N] [--random xorshift|vip] [--movie FILE]";
+
+enum RunLength {
+    Cycles(u64),
+    Frames(u64),
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let mut rom_path = None;
+    let mut length = RunLength::Frames(60);
+    let mut cpu_hz = DEFAULT_CPU_HZ;
+    let mut platform = Platform::default();
+    let mut quirks: Option<Quirks> = None;
+    let mut wav_path = None;
+    let mut beeper = BeeperConfig::default();
+    let mut movie_path = None;
+    let mut random = RandomKind::default();
+    let mut seed = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--cycles" => length = RunLength::Cycles(parse_value(args.next())),
+            "--frames" => length = RunLength::Frames(parse_value(args.next())),
+            "--hz" => cpu_hz = parse_value::<u32>(args.next()).max(1),
+            "--platform" => platform = parse_value(args.next()),
+            "--quirks" => quirks = Some(parse_value(args.next())),
+            "--wav" => wav_path = Some(parse_value::<String>(args.next())),
+            "--pitch" => beeper.pitch = parse_value(args.next()),
+            "--volume" => beeper.volume = parse_value(args.next()),
+            "--mute" => beeper.muted = true,
+            "--seed" => seed = Some(parse_value(args.next())),
+            "--random" => random = parse_value(args.next()),
+            "--movie" => movie_path = Some(parse_value::<String>(args.next())),
+            _ if rom_path.is_none() => rom_path = Some(arg),
+            _ => exit_with_usage(),
+        }
+    }
+
+    let Some(rom_path) = rom_path else {
+        exit_with_usage();
+    };
+
+    let rom = match fs::read(rom_path) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Could not read {}: {}", rom_path, err);
+            process::exit(1);
+        }
+    };
+
+    let movie = movie_path.map(|path| {
+        let movie = fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| Movie::from_bytes(&data, &sha1(&rom)).map_err(|err| err.to_string()));
+        match movie {
+            Ok(movie) => movie,
+            Err(err) => {
+                eprintln!("Could not replay {}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    });
+
+    // A movie brings its own platform, quirks and seed.
+    let mut chip8 = match &movie {
+        Some(movie) => movie.machine(),
+        None => {
+            let mut chip8 = Chip8::with_platform(platform);
+            if let Some(quirks) = quirks {
+                chip8.quirks = quirks;
+            }
+            if let Some(seed) = seed {
+                chip8.random = random.source(seed);
+            } else if random != RandomKind::default() {
+                chip8.random = random.source(rand::random());
+            }
+            chip8
+        }
+    };
+    if let Err(err) = chip8.load_rom(&rom) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+
+    let mut recorder = ToneRecorder::new(beeper);
+    let fault = match &movie {
+        Some(movie) => play_movie(&mut chip8, movie, &mut recorder),
+        None => run(&mut chip8, length, cpu_hz, &mut recorder),
+    };
+
+    print_display(&chip8);
+    print_registers(&chip8);
+
+    if let Some(wav_path) = wav_path {
+        let written =
+            File::create(&wav_path).and_then(|file| recorder.write_wav(BufWriter::new(file)));
+        if let Err(err) = written {
+            eprintln!("Could not write {}: {}", wav_path, err);
+            process::exit(1);
+        }
+    }
+
+    if let Some(err) = fault {
+        eprintln!("Halted: {}", err);
+        process::exit(2);
+    }
+}
+
+// Runs frame by frame on the same clock as the emulator window, so a run
+// executes as many instructions as it would there.
+fn run(
+    chip8: &mut Chip8,
+    length: RunLength,
+    cpu_hz: u32,
+    recorder: &mut ToneRecorder,
+) -> Option<Chip8Error> {
+    let mut clock = Clock::new(cpu_hz);
+    let (mut cycles_left, mut frames_left) = match length {
+        RunLength::Cycles(n) => (n, u64::MAX),
+        RunLength::Frames(n) => (u64::MAX, n),
+    };
+
+    let mut frame = 0;
+    while cycles_left > 0 && frames_left > 0 {
+        let due = u64::from(clock.advance(frame_duration(frame)));
+        let steps = due.min(cycles_left);
+        for _ in 0..steps {
+            if let Err(err) = chip8.step() {
+                return Some(err);
+            }
+            if chip8.exited {
+                return None;
+            }
+        }
+        // A cycle count that ends mid-frame leaves the timers alone.
+        if steps < due {
+            break;
+        }
+        cycles_left -= steps;
+        frames_left -= 1;
+        frame += 1;
+        recorder.record_frame(chip8);
+        chip8.tick_timers();
+    }
+    None
+}
+
+// The length of frame `frame`, rounded so that every FRAME_HZ frames add up
+// to exactly one second.
+fn frame_duration(frame: u64) -> Duration {
+    let start = frame * NANOS_PER_SEC / FRAME_HZ;
+    let end = (frame + 1) * NANOS_PER_SEC / FRAME_HZ;
+    Duration::from_nanos(end - start)
+}
+
+// Replays every frame of a movie recorded by the emulator.
+fn play_movie(chip8: &mut Chip8, movie: &Movie, recorder: &mut ToneRecorder) -> Option<Chip8Error> {
+    for frame in &movie.frames {
+        if let Err(err) = frame.play(chip8) {
+            return Some(err);
+        }
+        if chip8.exited {
+            break;
+        }
+        recorder.record_frame(chip8);
+    }
+    None
+}
+
+// Exits with the reason and the usage text if `arg` is missing or invalid.
+fn parse_value<T>(arg: Option<&String>) -> T
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let Some(arg) = arg else {
+        exit_with_usage();
+    };
+    match arg.parse() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit_with_usage();
+        }
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
+
+fn print_display(chip8: &Chip8) {
+    let (width, height) = chip8.display_size();
+    let border = format!("+{}+", "-".repeat(width));
+
+    println!("{}", border);
+    for y in 0..height {
+        let line: String = (0..width)
+            .map(|x| if chip8.pixel(x, y) { '#' } else { ' ' })
+            .collect();
+        println!("|{}|", line);
+    }
+    println!("{}", border);
+}
+
+fn print_registers(chip8: &Chip8) {
+    for (i, chunk) in chip8.V.chunks(8).enumerate() {
+        let regs: Vec<String> = chunk
+            .iter()
+            .enumerate()
+            .map(|(j, v)| format!("V{:X}={:02X}", i * 8 + j, v))
+            .collect();
+        println!("{}", regs.join(" "));
+    }
+    println!(
+        "I={:03X} pc={:03X} sp={:X} delay={} sound={}",
+        chip8.I, chip8.pc, chip8.sp, chip8.delay_timer, chip8.sound_timer
+    );
+}
@@ -0,0 +1,374 @@
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+use crate::cpu::Chip8;
+use crate::error::Chip8Error;
+use crate::instruction::Instruction;
+
+/// A register a watch or condition looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+}
+
+impl Register {
+    fn read(self, chip8: &Chip8) -> u16 {
+        match self {
+            Register::V(x) => chip8.V[x as usize].into(),
+            Register::I => chip8.I,
+        }
+    }
+}
+
+/// Comparison used by [`Breakpoint::Condition`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compare {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Compare {
+    const OPERATORS: [(&'static str, Compare); 6] = [
+        ("==", Compare::Eq),
+        ("!=", Compare::Ne),
+        ("<=", Compare::Le),
+        (">=", Compare::Ge),
+        ("<", Compare::Lt),
+        (">", Compare::Gt),
+    ];
+
+    fn holds(self, left: u16, right: u16) -> bool {
+        match self {
+            Compare::Eq => left == right,
+            Compare::Ne => left != right,
+            Compare::Lt => left < right,
+            Compare::Le => left <= right,
+            Compare::Gt => left > right,
+            Compare::Ge => left >= right,
+        }
+    }
+}
+
+/// Which memory accesses a watchpoint reacts to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+/// Something that pauses execution, see [`Breakpoints`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Stops before the instruction at this address runs.
+    Pc(u16),
+    /// Stops after an instruction reads or writes this address through `I`
+    /// (`Dxyn`, `Fx33`, `Fx55`, `Fx65`, `5xy2`, `5xy3`, `F002`).
+    Memory(u16, Access),
+    /// Stops after an instruction changes the register.
+    Change(Register),
+    /// Stops after an instruction makes the comparison true.
+    Condition(Register, Compare, u16),
+}
+
+/// Why [`Breakpoints::step`] stopped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakReason {
+    Pc(u16),
+    Read {
+        addr: u16,
+        pc: u16,
+    },
+    Write {
+        addr: u16,
+        pc: u16,
+    },
+    Changed {
+        register: Register,
+        old: u16,
+        new: u16,
+    },
+    Condition(Register, Compare, u16),
+}
+
+/// A set of breakpoints checked around every instruction.
+#[derive(Debug, Clone, Default)]
+pub struct Breakpoints {
+    list: Vec<Breakpoint>,
+    // The PC breakpoint just reported. Stepping again runs the instruction
+    // instead of stopping in front of it forever.
+    resume_pc: Option<u16>,
+}
+
+impl Breakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a breakpoint unless an identical one is already set.
+    pub fn add(&mut self, breakpoint: Breakpoint) {
+        if !self.list.contains(&breakpoint) {
+            self.list.push(breakpoint);
+        }
+    }
+
+    pub fn remove(&mut self, breakpoint: &Breakpoint) {
+        self.list.retain(|other| other != breakpoint);
+    }
+
+    pub fn clear(&mut self) {
+        self.list.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Breakpoint> {
+        self.list.iter()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.list.is_empty()
+    }
+
+    /// Executes one instruction like [`Chip8::step`] and reports the first
+    /// breakpoint it hit, if any.
+    ///
+    /// A PC breakpoint stops before its instruction runs: either when this
+    /// is called with `pc` on it, or right after the instruction that got
+    /// there. Calling `step` again then runs it.
+    pub fn step(&mut self, chip8: &mut Chip8) -> Result<Option<BreakReason>, Chip8Error> {
+        let pc = chip8.pc;
+        // A blocked Fx0A is still the same instruction, which already
+        // stopped once when it started.
+        let resumed = self.resume_pc.take() == Some(pc);
+        if !resumed && chip8.key_wait.is_none() && self.has_pc(pc) {
+            self.resume_pc = Some(pc);
+            return Ok(Some(BreakReason::Pc(pc)));
+        }
+
+        if self.list.is_empty() {
+            return chip8.step().map(|()| None);
+        }
+
+        let access = chip8
+            .instruction_at(pc)
+            .and_then(|instruction| memory_access(chip8, instruction));
+        let registers: Vec<(Register, u16)> = self
+            .list
+            .iter()
+            .filter_map(|breakpoint| match *breakpoint {
+                Breakpoint::Change(register) | Breakpoint::Condition(register, ..) => {
+                    Some((register, register.read(chip8)))
+                }
+                _ => None,
+            })
+            .collect();
+
+        chip8.step()?;
+
+        let mut registers = registers.into_iter();
+        for breakpoint in &self.list {
+            let reason = match *breakpoint {
+                Breakpoint::Pc(_) => None,
+                Breakpoint::Memory(addr, watch) => match &access {
+                    Some((range, kind)) if range.contains(&(addr as usize)) => {
+                        match (kind, watch) {
+                            (Access::Read, Access::Read | Access::ReadWrite) => {
+                                Some(BreakReason::Read { addr, pc })
+                            }
+                            (Access::Write, Access::Write | Access::ReadWrite) => {
+                                Some(BreakReason::Write { addr, pc })
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                },
+                Breakpoint::Change(register) => {
+                    let (_, old) = registers.next().unwrap_or((register, 0));
+                    let new = register.read(chip8);
+                    (old != new).then_some(BreakReason::Changed { register, old, new })
+                }
+                // Only the instruction that makes the condition true stops,
+                // so resuming does not stop again straight away.
+                Breakpoint::Condition(register, compare, value) => {
+                    let (_, old) = registers.next().unwrap_or((register, 0));
+                    let new = register.read(chip8);
+                    (!compare.holds(old, value) && compare.holds(new, value))
+                        .then_some(BreakReason::Condition(register, compare, value))
+                }
+            };
+            if reason.is_some() {
+                return Ok(reason);
+            }
+        }
+
+        // An instruction that does not move on, like a blocked Fx0A, has
+        // already been reported.
+        if chip8.pc != pc && self.has_pc(chip8.pc) {
+            self.resume_pc = Some(chip8.pc);
+            return Ok(Some(BreakReason::Pc(chip8.pc)));
+        }
+        Ok(None)
+    }
+
+    fn has_pc(&self, pc: u16) -> bool {
+        self.list.contains(&Breakpoint::Pc(pc))
+    }
+}
+
+// Memory the instruction about to run reads or writes through I.
+fn memory_access(chip8: &Chip8, instruction: Instruction) -> Option<(Range<usize>, Access)> {
+    let i = chip8.I as usize;
+    let (len, access) = match instruction {
+        Instruction::Draw(_, _, n) => (chip8.sprite_len(n), Access::Read),
+        Instruction::StoreBcd(_) => (3, Access::Write),
+        Instruction::StoreRegs(x) => (x as usize + 1, Access::Write),
+        Instruction::LoadRegs(x) => (x as usize + 1, Access::Read),
+        Instruction::SaveRange(x, y) => (x.abs_diff(y) as usize + 1, Access::Write),
+        Instruction::LoadRange(x, y) => (x.abs_diff(y) as usize + 1, Access::Read),
+        Instruction::Audio => (16, Access::Read),
+        _ => return None,
+    };
+    Some((i..i + len, access))
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Register::V(x) => write!(f, "V{:X}", x),
+            Register::I => write!(f, "I"),
+        }
+    }
+}
+
+impl fmt::Display for Compare {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (operator, _) = Compare::OPERATORS
+            .iter()
+            .find(|(_, compare)| compare == self)
+            .expect("every comparison has an operator");
+        write!(f, "{}", operator)
+    }
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Breakpoint::Pc(addr) => write!(f, "pc {:#05X}", addr),
+            Breakpoint::Memory(addr, Access::Read) => write!(f, "r {:#05X}", addr),
+            Breakpoint::Memory(addr, Access::Write) => write!(f, "w {:#05X}", addr),
+            Breakpoint::Memory(addr, Access::ReadWrite) => write!(f, "rw {:#05X}", addr),
+            Breakpoint::Change(register) => write!(f, "{}", register),
+            Breakpoint::Condition(register, compare, value) => {
+                write!(f, "{} {} {:#04X}", register, compare, value)
+            }
+        }
+    }
+}
+
+impl fmt::Display for BreakReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BreakReason::Pc(addr) => write!(f, "breakpoint at {:#05X}", addr),
+            BreakReason::Read { addr, pc } => {
+                write!(f, "{:#05X} read by the instruction at {:#05X}", addr, pc)
+            }
+            BreakReason::Write { addr, pc } => {
+                write!(f, "{:#05X} written by the instruction at {:#05X}", addr, pc)
+            }
+            BreakReason::Changed {
+                register: Register::I,
+                old,
+                new,
+            } => write!(f, "I changed from {:#05X} to {:#05X}", old, new),
+            BreakReason::Changed { register, old, new } => {
+                write!(f, "{} changed from {:#04X} to {:#04X}", register, old, new)
+            }
+            BreakReason::Condition(register, compare, value) => {
+                write!(f, "{} {} {:#04X}", register, compare, value)
+            }
+        }
+    }
+}
+
+/// Returned when parsing a breakpoint fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidBreakpoint(pub String);
+
+impl fmt::Display for InvalidBreakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid breakpoint '{}', expected an address, 'r ADDR', 'w ADDR', \
+             'rw ADDR', a register such as 'V3' or 'I', or a condition such as 'V3 == 0x10'",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidBreakpoint {}
+
+impl FromStr for Register {
+    type Err = InvalidBreakpoint;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("i") {
+            return Ok(Register::I);
+        }
+        match s.strip_prefix(['v', 'V']) {
+            Some(digit) if digit.len() == 1 => u8::from_str_radix(digit, 16)
+                .map(Register::V)
+                .map_err(|_| InvalidBreakpoint(s.to_string())),
+            _ => Err(InvalidBreakpoint(s.to_string())),
+        }
+    }
+}
+
+/// Parses `0x2A4` or `pc 0x2A4`, `r 0x300`, `w 0x300`, `rw 0x300`, `V3`,
+/// `I` and conditions like `V3 == 0x10`. Numbers are hex with or without
+/// `0x`.
+impl FromStr for Breakpoint {
+    type Err = InvalidBreakpoint;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || InvalidBreakpoint(s.to_string());
+        let spec = s.trim();
+
+        for (operator, compare) in Compare::OPERATORS {
+            if let Some((register, value)) = spec.split_once(operator) {
+                let register = register.parse().map_err(|_| invalid())?;
+                let value = parse_hex(value).ok_or_else(invalid)?;
+                return Ok(Breakpoint::Condition(register, compare, value));
+            }
+        }
+
+        if let Ok(register) = spec.parse() {
+            return Ok(Breakpoint::Change(register));
+        }
+
+        let (kind, addr) = spec.split_once(' ').unwrap_or(("pc", spec));
+        let addr = parse_hex(addr).ok_or_else(invalid)?;
+        match kind.to_ascii_lowercase().as_str() {
+            "pc" => Ok(Breakpoint::Pc(addr)),
+            "r" => Ok(Breakpoint::Memory(addr, Access::Read)),
+            "w" => Ok(Breakpoint::Memory(addr, Access::Write)),
+            "rw" => Ok(Breakpoint::Memory(addr, Access::ReadWrite)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn parse_hex(s: &str) -> Option<u16> {
+    let s = s.trim();
+    let digits = s
+        .strip_prefix("0x")
+        .or_else(|| s.strip_prefix("0X"))
+        .unwrap_or(s);
+    u16::from_str_radix(digits, 16).ok()
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+/// Default CPU speed, in instructions per second.
+pub const DEFAULT_CPU_HZ: u32 = 700;
+
+// Longer gaps (a window drag, a debugger pause) are clamped so the machine
+// does not try to catch up on seconds of missed work in one frame.
+const MAX_ELAPSED: Duration = Duration::from_millis(250);
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// Converts elapsed wall-clock time into a whole number of ticks at a fixed
+/// frequency, carrying the remainder over to the next call.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    hz: u32,
+    // Elapsed nanoseconds multiplied by `hz`, not yet turned into ticks.
+    remainder: u128,
+}
+
+impl Clock {
+    pub fn new(hz: u32) -> Self {
+        assert!(hz > 0, "clock frequency must be positive");
+        Self { hz, remainder: 0 }
+    }
+
+    pub fn hz(&self) -> u32 {
+        self.hz
+    }
+
+    pub fn set_hz(&mut self, hz: u32) {
+        assert!(hz > 0, "clock frequency must be positive");
+        self.hz = hz;
+        self.remainder = 0;
+    }
+
+    /// Returns how many ticks are due after `elapsed` more time has passed.
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        let elapsed = elapsed.min(MAX_ELAPSED);
+        self.remainder += elapsed.as_nanos() * self.hz as u128;
+
+        let ticks = self.remainder / NANOS_PER_SEC;
+        self.remainder %= NANOS_PER_SEC;
+        ticks as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carries_fractions_of_a_tick() {
+        let mut clock = Clock::new(700);
+        // 700 Hz is 10/6 ticks per 60 Hz frame, so a tick is left over until
+        // the sixth frame makes the remainders add up.
+        let ticks: Vec<u32> = (0..6)
+            .map(|_| clock.advance(Duration::from_nanos(16_666_667)))
+            .collect();
+        assert_eq!(ticks.iter().sum::<u32>(), 70);
+        assert!(ticks.iter().all(|&n| n == 11 || n == 12));
+    }
+
+    #[test]
+    fn adds_up_to_the_frequency_over_a_second() {
+        let mut clock = Clock::new(700);
+        let total: u32 = (0..100)
+            .map(|_| clock.advance(Duration::from_millis(10)))
+            .sum();
+        assert_eq!(total, 700);
+    }
+
+    #[test]
+    fn clamps_long_gaps() {
+        let mut clock = Clock::new(1000);
+        assert_eq!(clock.advance(Duration::from_secs(5)), 250);
+        assert_eq!(clock.advance(MAX_ELAPSED), 250);
+    }
+
+    #[test]
+    fn changing_the_frequency_drops_the_remainder() {
+        let mut clock = Clock::new(60);
+        assert_eq!(clock.advance(Duration::from_millis(10)), 0);
+        clock.set_hz(100);
+        assert_eq!(clock.hz(), 100);
+        assert_eq!(clock.advance(Duration::from_millis(9)), 0);
+        assert_eq!(clock.advance(Duration::from_millis(1)), 1);
+    }
+}
@@ -0,0 +1,814 @@
+use crate::audio::DEFAULT_PITCH;
+use crate::error::{Chip8Error, RomError};
+use crate::font::{BIG_FONTSET, BIG_FONT_START, FONTSET, FONT_START};
+use crate::instruction::Instruction;
+use crate::platform::Platform;
+use crate::quirks::{IndexIncrement, Quirks};
+use crate::random::{RandomKind, RandomSource};
+
+pub const MEMORY_SIZE: usize = 4096;
+pub const XO_MEMORY_SIZE: usize = 0x10000;
+pub const PROGRAM_START: usize = 0x200;
+pub const DISPLAY_WIDTH: usize = 64;
+pub const DISPLAY_HEIGHT: usize = 32;
+// SUPER-CHIP hi-res mode. In lo-res mode only the top-left
+// DISPLAY_WIDTH x DISPLAY_HEIGHT corner of `display` is used.
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+/// An `Fx0A` that is blocking the machine until a key is pressed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyWait {
+    /// The register the key goes into.
+    pub x: u8,
+    /// The key that went down, once one has and the machine is waiting for
+    /// it to come back up. Only used with [`Quirks::wait_for_release`].
+    pub key: Option<u8>,
+    /// Bit `n` is set for keys that were already down when `Fx0A` started.
+    /// They only count once they have been released and pressed again.
+    pub held: u16,
+}
+
+/// The complete state of a CHIP-8 machine.
+#[allow(non_snake_case)]
+#[derive(Clone)]
+pub struct Chip8 {
+    pub memory: Vec<u8>, // 4 KB Memory, 64 KB on XO-CHIP
+    pub opcode: u16,
+    pub V: [u8; 16], // General Registers
+    pub I: u16,      // Special Register
+    pub pc: u16,     // Program Counter
+    // Each pixel holds one bit per bitplane. Only XO-CHIP uses the second plane.
+    pub display: [[u8; HIRES_WIDTH]; HIRES_HEIGHT],
+    pub hires: bool,
+    pub planes: u8, // Bitplanes selected by Fn01
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub stack: [u16; 16],
+    pub sp: u8, // Stack Pointer
+    pub keypad: [u8; 16],
+    pub redraw: bool,
+    pub quirks: Quirks,
+    pub platform: Platform,
+    pub rpl: [u8; 16],                   // SUPER-CHIP user flags
+    pub exited: bool,                    // Set by 00FD
+    pub audio_pattern: Option<[u8; 16]>, // XO-CHIP audio pattern loaded by F002
+    pub pitch: u8,                       // XO-CHIP playback pitch set by Fx3A
+    pub random: Box<dyn RandomSource>,   // Source of Cxkk's random bytes
+    pub key_wait: Option<KeyWait>,       // Set while Fx0A blocks, pc stays on it
+}
+
+impl Default for Chip8 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8 {
+    /// Creates a CHIP-8 machine with the font loaded and `pc` at the program start.
+    pub fn new() -> Self {
+        Self::with_platform(Platform::default())
+    }
+
+    /// Creates a machine for `platform`, with memory sized accordingly and
+    /// the quirks its ROMs usually expect.
+    pub fn with_platform(platform: Platform) -> Self {
+        let mut chip8 = Self {
+            memory: vec![0; platform.memory_size()],
+            opcode: 0,
+            V: [0; 16],
+            I: 0,
+            pc: PROGRAM_START as u16,
+            display: [[0; HIRES_WIDTH]; HIRES_HEIGHT],
+            hires: false,
+            planes: 1,
+            delay_timer: 0,
+            sound_timer: 0,
+            stack: [0; 16],
+            sp: 0,
+            keypad: [0; 16],
+            redraw: false,
+            quirks: platform.default_quirks(),
+            platform,
+            rpl: [0; 16],
+            exited: false,
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            random: RandomKind::default().source(rand::random()),
+            key_wait: None,
+        };
+        chip8.load_font();
+        chip8
+    }
+
+    /// Writes the built-in hex digit sprites, small and big, to the start of memory.
+    pub fn load_font(&mut self) {
+        self.memory[FONT_START..FONT_START + FONTSET.len()].copy_from_slice(&FONTSET);
+        self.memory[BIG_FONT_START..BIG_FONT_START + BIG_FONTSET.len()]
+            .copy_from_slice(&BIG_FONTSET);
+    }
+
+    /// Copies a ROM image into memory at [`PROGRAM_START`].
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        let max = self.memory.len() - PROGRAM_START;
+        if rom.len() > max {
+            return Err(RomError::TooLarge {
+                size: rom.len(),
+                max,
+            });
+        }
+
+        self.memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    /// Fetches, decodes and executes a single instruction.
+    ///
+    /// On error the machine is left as it was before the faulting
+    /// instruction, so `pc` still points at it. Once a SUPER-CHIP program
+    /// has exited with `00FD`, this does nothing. While `Fx0A` blocks, a
+    /// step only looks at the keypad; the timers keep running regardless.
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        if self.exited {
+            return Ok(());
+        }
+        if let Some(wait) = self.key_wait {
+            self.poll_key_wait(wait);
+            return Ok(());
+        }
+
+        let pc = self.pc as usize;
+        if pc + 1 >= self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds {
+                pc: self.pc,
+                opcode: 0,
+                addr: pc,
+            });
+        }
+        self.opcode = u16::from(self.memory[pc]) << 8 | u16::from(self.memory[pc + 1]);
+
+        // Only F000 nnnn reads the word after the opcode. It checks the
+        // bounds itself, so a missing word can decode as 0 here.
+        let next = match self.memory.get(pc + 2..pc + 4) {
+            Some(bytes) => u16::from(bytes[0]) << 8 | u16::from(bytes[1]),
+            None => 0,
+        };
+        let instruction = match Instruction::decode(self.opcode, next, self.platform) {
+            Some(instruction) => instruction,
+            None => return Err(self.unknown_opcode()),
+        };
+
+        self.execute(instruction)
+    }
+
+    fn execute(&mut self, instruction: Instruction) -> Result<(), Chip8Error> {
+        match instruction {
+            // Clear the display.
+            Instruction::Cls => {
+                self.clear_display();
+                self.advance(2);
+            }
+
+            // Scroll the display down n lines. (SUPER-CHIP)
+            Instruction::ScrollDown(n) => {
+                self.scroll(0, n as isize);
+                self.advance(2);
+            }
+
+            // Scroll the display up n lines. (XO-CHIP)
+            Instruction::ScrollUp(n) => {
+                self.scroll(0, -(n as isize));
+                self.advance(2);
+            }
+
+            // Scroll the display right 4 pixels. (SUPER-CHIP)
+            Instruction::ScrollRight => {
+                self.scroll(4, 0);
+                self.advance(2);
+            }
+
+            // Scroll the display left 4 pixels. (SUPER-CHIP)
+            Instruction::ScrollLeft => {
+                self.scroll(-4, 0);
+                self.advance(2);
+            }
+
+            // Exit the interpreter. (SUPER-CHIP)
+            Instruction::Exit => {
+                self.exited = true;
+            }
+
+            // Switch to lo-res (64x32) or hi-res (128x64) mode. (SUPER-CHIP)
+            Instruction::Low | Instruction::High => {
+                self.hires = instruction == Instruction::High;
+                self.display = [[0; HIRES_WIDTH]; HIRES_HEIGHT];
+                self.redraw = true;
+                self.advance(2);
+            }
+
+            // Return from a subroutine.
+            Instruction::Ret => {
+                if self.sp == 0 {
+                    return Err(Chip8Error::StackUnderflow {
+                        pc: self.pc,
+                        opcode: self.opcode,
+                    });
+                }
+                self.sp -= 1;
+                self.pc = self.stack[self.sp as usize];
+                self.advance(2);
+            }
+
+            // Jump to location nnn.
+            Instruction::Jump(nnn) => {
+                self.pc = nnn;
+            }
+
+            // Call subroutine at nnn.
+            Instruction::Call(nnn) => {
+                if self.sp as usize >= self.stack.len() {
+                    return Err(Chip8Error::StackOverflow {
+                        pc: self.pc,
+                        opcode: self.opcode,
+                    });
+                }
+                self.stack[self.sp as usize] = self.pc;
+                self.sp += 1;
+                self.pc = nnn;
+            }
+
+            // Skip next instruction if Vx = kk.
+            Instruction::SkipEqByte(x, kk) => {
+                if self.V[x as usize] == kk {
+                    self.skip_next()
+                }
+                self.advance(2);
+            }
+
+            // Skip next instruction if Vx != kk.
+            Instruction::SkipNeByte(x, kk) => {
+                if self.V[x as usize] != kk {
+                    self.skip_next()
+                }
+                self.advance(2);
+            }
+
+            // Skip next instruction if Vx = Vy.
+            Instruction::SkipEqReg(x, y) => {
+                if self.V[x as usize] == self.V[y as usize] {
+                    self.skip_next()
+                }
+                self.advance(2);
+            }
+
+            // Store Vx through Vy in memory starting at location I. (XO-CHIP)
+            Instruction::SaveRange(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                let addr = self.I as usize;
+
+                self.check_memory(addr, x.abs_diff(y) + 1)?;
+                for (offset, reg) in register_range(x, y).enumerate() {
+                    self.memory[addr + offset] = self.V[reg];
+                }
+                self.advance(2);
+            }
+
+            // Read Vx through Vy from memory starting at location I. (XO-CHIP)
+            Instruction::LoadRange(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                let addr = self.I as usize;
+
+                self.check_memory(addr, x.abs_diff(y) + 1)?;
+                for (offset, reg) in register_range(x, y).enumerate() {
+                    self.V[reg] = self.memory[addr + offset];
+                }
+                self.advance(2);
+            }
+
+            // Set Vx = kk.
+            Instruction::LoadByte(x, kk) => {
+                self.V[x as usize] = kk;
+
+                self.advance(2);
+            }
+
+            // Set Vx = Vx + kk.
+            Instruction::AddByte(x, kk) => {
+                let x = x as usize;
+                self.V[x] = self.V[x].wrapping_add(kk);
+
+                self.advance(2);
+            }
+
+            // Set Vx = Vy.
+            Instruction::LoadReg(x, y) => {
+                self.V[x as usize] = self.V[y as usize];
+
+                self.advance(2);
+            }
+
+            // Set Vx = Vx OR Vy. (Bitwise OR)
+            Instruction::Or(x, y) => {
+                self.V[x as usize] |= self.V[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.V[0xF] = 0;
+                }
+
+                self.advance(2);
+            }
+
+            // Set Vx = Vx AND Vy.
+            Instruction::And(x, y) => {
+                self.V[x as usize] &= self.V[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.V[0xF] = 0;
+                }
+
+                self.advance(2);
+            }
+
+            // Set Vx = Vx XOR Vy.
+            Instruction::Xor(x, y) => {
+                self.V[x as usize] ^= self.V[y as usize];
+                if self.quirks.logic_resets_vf {
+                    self.V[0xF] = 0;
+                }
+
+                self.advance(2);
+            }
+
+            // Set Vx = Vx + Vy, set VF = carry.
+            Instruction::AddReg(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                let (sum, carry) = self.V[x].overflowing_add(self.V[y]);
+
+                // VF is written last so it wins when x is F.
+                self.V[x] = sum;
+                self.V[0xF] = carry as u8;
+
+                self.advance(2);
+            }
+
+            // Set Vx = Vx - Vy, set VF = NOT borrow.
+            Instruction::Sub(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                let not_borrow = self.V[x] >= self.V[y];
+
+                self.V[x] = self.V[x].wrapping_sub(self.V[y]);
+                self.V[0xF] = not_borrow as u8;
+
+                self.advance(2);
+            }
+
+            // Set Vx = Vx SHR 1 (or Vy SHR 1, see Quirks::shift_uses_vy).
+            Instruction::ShiftRight(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                let value = if self.quirks.shift_uses_vy {
+                    self.V[y]
+                } else {
+                    self.V[x]
+                };
+                self.V[x] = value >> 1;
+                self.V[0xF] = value & 0x1;
+
+                self.advance(2);
+            }
+
+            // Set Vx = Vy - Vx, set VF = NOT borrow.
+            Instruction::SubReverse(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                let not_borrow = self.V[y] >= self.V[x];
+
+                self.V[x] = self.V[y].wrapping_sub(self.V[x]);
+                self.V[0xF] = not_borrow as u8;
+
+                self.advance(2);
+            }
+
+            // Stores the most significant bit of VX in VF and then shifts VX to the left by 1.
+            // Shifts Vy instead when Quirks::shift_uses_vy is set.
+            Instruction::ShiftLeft(x, y) => {
+                let (x, y) = (x as usize, y as usize);
+                let value = if self.quirks.shift_uses_vy {
+                    self.V[y]
+                } else {
+                    self.V[x]
+                };
+                self.V[x] = value << 1;
+                self.V[0xF] = value >> 7;
+
+                self.advance(2);
+            }
+
+            // Skip next instruction if Vx != Vy.
+            Instruction::SkipNeReg(x, y) => {
+                if self.V[x as usize] != self.V[y as usize] {
+                    self.skip_next();
+                }
+
+                self.advance(2);
+            }
+
+            // Set I = nnn.
+            Instruction::LoadIndex(nnn) => {
+                self.I = nnn;
+                self.advance(2);
+            }
+
+            // Jump to location nnn + V0 (or xnn + Vx, see Quirks::jump_uses_vx).
+            Instruction::JumpOffset(nnn) => {
+                let offset = if self.quirks.jump_uses_vx {
+                    self.V[(nnn >> 8) as usize]
+                } else {
+                    self.V[0]
+                };
+                self.pc = nnn + offset as u16;
+            }
+
+            // Set Vx = random byte AND kk.
+            Instruction::Random(x, kk) => {
+                let rand_num = self.random.next_byte(&self.memory);
+
+                self.V[x as usize] = rand_num & kk;
+                self.advance(2);
+            }
+
+            // Display n-byte sprite starting at memory location I at (Vx, Vy), set VF = collision.
+            // Sprites are 8 pixels wide and N pixels high, or 16x16 for Dxy0 on SUPER-CHIP.
+            Instruction::Draw(x, y, n) => {
+                let (width, rows) = self.sprite_shape(n);
+                self.check_memory(self.I as usize, self.sprite_len(n))?;
+
+                self.draw_sprite(self.V[x as usize], self.V[y as usize], width, rows);
+                self.advance(2);
+            }
+
+            // Skip next instruction if key with the value of Vx is pressed.
+            Instruction::SkipKey(x) => {
+                let key = self.key_index(self.V[x as usize])?;
+                if self.keypad[key] == 1 {
+                    self.skip_next()
+                }
+                self.advance(2);
+            }
+
+            // Skip next instruction if key with the value of Vx is not pressed.
+            Instruction::SkipNotKey(x) => {
+                let key = self.key_index(self.V[x as usize])?;
+                if self.keypad[key] == 0 {
+                    self.skip_next()
+                }
+                self.advance(2);
+            }
+
+            // Set I = nnnn, the 16-bit word following this instruction. (XO-CHIP)
+            Instruction::LoadLongIndex(nnnn) => {
+                self.check_memory(self.pc as usize + 2, 2)?;
+
+                self.I = nnnn;
+                self.advance(4);
+            }
+
+            // Select the bitplanes n that drawing, clearing and scrolling affect. (XO-CHIP)
+            Instruction::Plane(n) => {
+                self.planes = n & 0x3;
+                self.advance(2);
+            }
+
+            // Load the 16-byte audio pattern at I. (XO-CHIP)
+            Instruction::Audio => {
+                let addr = self.I as usize;
+                self.check_memory(addr, 16)?;
+
+                let mut pattern = [0; 16];
+                pattern.copy_from_slice(&self.memory[addr..addr + 16]);
+                self.audio_pattern = Some(pattern);
+                self.advance(2);
+            }
+
+            // Set Vx = delay timer value.
+            Instruction::LoadDelay(x) => {
+                self.V[x as usize] = self.delay_timer;
+                self.advance(2);
+            }
+
+            // Wait for a key press, store the value of the key in Vx. The
+            // COSMAC VIP also waits for the key to be released again.
+            Instruction::WaitKey(x) => {
+                let held = self
+                    .keypad
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, &key)| key != 0)
+                    .fold(0, |held, (i, _)| held | 1 << i);
+                self.poll_key_wait(KeyWait { x, key: None, held });
+            }
+
+            // Set delay timer = Vx.
+            Instruction::SetDelay(x) => {
+                self.delay_timer = self.V[x as usize];
+                self.advance(2);
+            }
+
+            // Set sound timer = Vx.
+            Instruction::SetSound(x) => {
+                self.sound_timer = self.V[x as usize];
+                self.advance(2);
+            }
+
+            // Set I = I + Vx.
+            Instruction::AddIndex(x) => {
+                self.I = self.I.wrapping_add(self.V[x as usize] as u16);
+
+                if self.quirks.index_overflow_sets_vf {
+                    self.V[0xF] = (self.I > 0xFFF) as u8;
+                }
+                self.advance(2);
+            }
+
+            // Set I = location of sprite for digit Vx.
+            Instruction::LoadFont(x) => {
+                self.I = FONT_START as u16 + (self.V[x as usize] as u16 & 0xF) * 5;
+                self.advance(2);
+            }
+
+            // Set I = location of the 10-byte sprite for digit Vx. (SUPER-CHIP)
+            Instruction::LoadBigFont(x) => {
+                self.I = BIG_FONT_START as u16 + (self.V[x as usize] as u16 & 0xF) * 10;
+                self.advance(2);
+            }
+
+            // Set the audio pattern playback pitch = Vx. (XO-CHIP)
+            Instruction::Pitch(x) => {
+                self.pitch = self.V[x as usize];
+                self.advance(2);
+            }
+
+            // Store BCD representation of Vx in memory locations I, I+1, and I+2.
+            Instruction::StoreBcd(x) => {
+                let value = self.V[x as usize];
+                let i = self.I as usize;
+                self.check_memory(i, 3)?;
+                self.memory[i] = value / 100;
+                self.memory[i + 1] = (value / 10) % 10;
+                self.memory[i + 2] = value % 10;
+
+                self.advance(2);
+            }
+
+            // Store registers V0 through Vx in memory starting at location I.
+            Instruction::StoreRegs(x) => {
+                let x = x as usize;
+                let addr = self.I as usize;
+                self.check_memory(addr, x + 1)?;
+                self.memory[addr..=addr + x].copy_from_slice(&self.V[..=x]);
+
+                self.increment_index(x);
+                self.advance(2);
+            }
+
+            // Read registers V0 through Vx from memory starting at location I.
+            Instruction::LoadRegs(x) => {
+                let x = x as usize;
+                let addr = self.I as usize;
+                self.check_memory(addr, x + 1)?;
+                self.V[..=x].copy_from_slice(&self.memory[addr..=addr + x]);
+
+                self.increment_index(x);
+                self.advance(2);
+            }
+
+            // Store V0 through Vx in the RPL user flags. (SUPER-CHIP)
+            Instruction::StoreFlags(x) => {
+                let x = x as usize;
+                self.rpl[..=x].copy_from_slice(&self.V[..=x]);
+                self.advance(2);
+            }
+
+            // Read V0 through Vx from the RPL user flags. (SUPER-CHIP)
+            Instruction::LoadFlags(x) => {
+                let x = x as usize;
+                self.V[..=x].copy_from_slice(&self.rpl[..=x]);
+                self.advance(2);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Width and height of the sprite Dxyn draws.
+    fn sprite_shape(&self, n: u8) -> (usize, usize) {
+        if n == 0 && self.platform.has_schip() {
+            (16, 16)
+        } else {
+            (8, n as usize)
+        }
+    }
+
+    // Bytes Dxyn reads from I, one sprite per selected bitplane.
+    pub(crate) fn sprite_len(&self, n: u8) -> usize {
+        let (width, rows) = self.sprite_shape(n);
+        self.planes.count_ones() as usize * rows * width / 8
+    }
+
+    // Moves I past the registers stored or loaded by Fx55/Fx65.
+    fn increment_index(&mut self, x: usize) {
+        match self.quirks.index_increment {
+            IndexIncrement::Unchanged => (),
+            IndexIncrement::ByX => self.I = self.I.wrapping_add(x as u16),
+            IndexIncrement::ByXPlusOne => self.I = self.I.wrapping_add(x as u16 + 1),
+        }
+    }
+
+    // Moves pc past `len` bytes of code. XO-CHIP programs may fill all
+    // 64 KB, and pc wraps around to 0 after the last address.
+    fn advance(&mut self, len: u16) {
+        self.pc = self.pc.wrapping_add(len);
+    }
+
+    // Skips the instruction after the current one. On XO-CHIP that may be
+    // the 4-byte F000 nnnn.
+    fn skip_next(&mut self) {
+        let next = self.pc.wrapping_add(2) as usize;
+        let long = self.platform.has_xo()
+            && next + 1 < self.memory.len()
+            && self.memory[next] == 0xF0
+            && self.memory[next + 1] == 0x00;
+
+        self.advance(if long { 4 } else { 2 });
+    }
+
+    // Moves a blocked Fx0A along and finishes it once a key has gone down,
+    // and back up if the quirk asks for it.
+    fn poll_key_wait(&mut self, mut wait: KeyWait) {
+        for (i, &key) in self.keypad.iter().enumerate() {
+            if key == 0 {
+                wait.held &= !(1 << i);
+            }
+        }
+        let held = wait.held;
+        let done = match wait.key {
+            None => match (0..16).find(|&i| self.keypad[i] == 1 && held & 1 << i == 0) {
+                Some(key) if self.quirks.wait_for_release => {
+                    wait.key = Some(key as u8);
+                    None
+                }
+                Some(key) => Some(key as u8),
+                None => None,
+            },
+            Some(key) if self.keypad[key as usize] == 0 => Some(key),
+            Some(_) => None,
+        };
+
+        match done {
+            Some(key) => {
+                self.V[wait.x as usize] = key;
+                self.key_wait = None;
+                self.advance(2);
+            }
+            None => self.key_wait = Some(wait),
+        }
+    }
+
+    fn unknown_opcode(&self) -> Chip8Error {
+        Chip8Error::UnknownOpcode {
+            pc: self.pc,
+            opcode: self.opcode,
+        }
+    }
+
+    // Fails unless `len` bytes starting at `addr` lie inside memory.
+    fn check_memory(&self, addr: usize, len: usize) -> Result<(), Chip8Error> {
+        if addr + len > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds {
+                pc: self.pc,
+                opcode: self.opcode,
+                addr: addr + len - 1,
+            });
+        }
+        Ok(())
+    }
+
+    fn key_index(&self, key: u8) -> Result<usize, Chip8Error> {
+        if key as usize >= self.keypad.len() {
+            return Err(Chip8Error::InvalidKey {
+                pc: self.pc,
+                opcode: self.opcode,
+                key,
+            });
+        }
+        Ok(key as usize)
+    }
+}
+
+// Registers x through y, counting down when x > y.
+fn register_range(x: usize, y: usize) -> impl Iterator<Item = usize> {
+    (0..=x.abs_diff(y)).map(move |i| if x <= y { x + i } else { x - i })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xo_chip_at(pc: u16, code: &[u8]) -> Chip8 {
+        let mut chip8 = Chip8::with_platform(Platform::XoChip);
+        chip8.memory[pc as usize..pc as usize + code.len()].copy_from_slice(code);
+        chip8.pc = pc;
+        chip8
+    }
+
+    #[test]
+    fn load_at_last_address_wraps_pc() {
+        let mut chip8 = xo_chip_at(0xFFFE, &[0x61, 0x2A]);
+        chip8.step().unwrap();
+        assert_eq!(chip8.V[1], 0x2A);
+        assert_eq!(chip8.pc, 0x0000);
+    }
+
+    #[test]
+    fn skip_at_last_address_wraps_pc() {
+        let mut chip8 = xo_chip_at(0xFFFE, &[0x30, 0x00]);
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x0002);
+    }
+
+    #[test]
+    fn skip_over_long_load_wraps_pc() {
+        let mut chip8 = xo_chip_at(0xFFFA, &[0x30, 0x00, 0xF0, 0x00, 0x12, 0x34]);
+        chip8.step().unwrap();
+        assert_eq!(chip8.pc, 0x0000);
+    }
+
+    // A machine that has just run `LD V3, K` with `held` down.
+    fn waiting_for_key(quirks: Quirks, held: &[usize]) -> Chip8 {
+        let mut chip8 = Chip8::with_platform(Platform::Chip8);
+        chip8.quirks = quirks;
+        chip8.load_rom(&[0xF3, 0x0A]).unwrap();
+        for &key in held {
+            chip8.keypad[key] = 1;
+        }
+        chip8.step().unwrap();
+        chip8
+    }
+
+    #[test]
+    fn key_wait_finishes_on_release() {
+        let mut chip8 = waiting_for_key(Quirks::COSMAC_VIP, &[]);
+        assert!(chip8.key_wait.is_some());
+
+        chip8.keypad[5] = 1;
+        chip8.step().unwrap();
+        assert_eq!(chip8.key_wait.map(|wait| wait.key), Some(Some(5)));
+        assert_eq!(chip8.pc, 0x200);
+
+        chip8.keypad[5] = 0;
+        chip8.step().unwrap();
+        assert_eq!(chip8.key_wait, None);
+        assert_eq!(chip8.V[3], 5);
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    fn key_wait_finishes_on_press_without_the_quirk() {
+        let mut chip8 = waiting_for_key(Quirks::CHIP_48, &[]);
+        chip8.keypad[7] = 1;
+        chip8.step().unwrap();
+        assert_eq!(chip8.key_wait, None);
+        assert_eq!(chip8.V[3], 7);
+        assert_eq!(chip8.pc, 0x202);
+    }
+
+    #[test]
+    fn key_held_at_the_wait_does_not_count() {
+        let mut chip8 = waiting_for_key(Quirks::CHIP_48, &[2]);
+        chip8.step().unwrap();
+        assert!(chip8.key_wait.is_some());
+
+        chip8.keypad[2] = 0;
+        chip8.step().unwrap();
+        assert!(chip8.key_wait.is_some());
+
+        chip8.keypad[2] = 1;
+        chip8.step().unwrap();
+        assert_eq!(chip8.key_wait, None);
+        assert_eq!(chip8.V[3], 2);
+
+        let mut chip8 = waiting_for_key(Quirks::COSMAC_VIP, &[2]);
+        chip8.keypad[2] = 0;
+        chip8.step().unwrap();
+        assert!(chip8.key_wait.is_some_and(|wait| wait.key.is_none()));
+    }
+
+    #[test]
+    fn timers_run_while_waiting_for_a_key() {
+        let mut chip8 = waiting_for_key(Quirks::COSMAC_VIP, &[]);
+        chip8.delay_timer = 10;
+        chip8.sound_timer = 2;
+        for _ in 0..3 {
+            chip8.step().unwrap();
+            chip8.tick_timers();
+        }
+        assert!(chip8.key_wait.is_some());
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (7, 0));
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::cpu::PROGRAM_START;
+use crate::instruction::Instruction;
+use crate::platform::Platform;
+
+// Bytes per data line in a listing.
+const DATA_BYTES_PER_LINE: usize = 4;
+
+/// A ROM split into labeled code and data, see [`disassemble`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Listing {
+    pub lines: Vec<Line>,
+}
+
+/// One line of a [`Listing`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub addr: u16,
+    /// Set when a jump or call in the program lands on `addr`.
+    pub label: Option<String>,
+    pub bytes: Vec<u8>,
+    /// `None` for bytes that are never reached as code.
+    pub instruction: Option<Instruction>,
+}
+
+/// Disassembles a ROM loaded at [`PROGRAM_START`].
+///
+/// Code is found by following every path from the entry point, including
+/// both sides of skips and the targets of jumps and calls, so sprites and
+/// other data in between are listed as `DB` bytes instead of nonsense
+/// instructions. Targets of `Bnnn` depend on `V0` and are not followed.
+pub fn disassemble(rom: &[u8], platform: Platform) -> Listing {
+    let decode = |addr: usize| -> Option<Instruction> {
+        let offset = addr.checked_sub(PROGRAM_START)?;
+        let word = |offset: usize| -> Option<u16> {
+            let bytes = rom.get(offset..offset + 2)?;
+            Some(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+        };
+        let next = word(offset + 2).unwrap_or(0);
+        let instruction = Instruction::decode(word(offset)?, next, platform)?;
+        // F000 nnnn must fit in the ROM as a whole.
+        (offset + instruction.size() <= rom.len()).then_some(instruction)
+    };
+
+    let mut code = BTreeMap::new();
+    let mut labels = BTreeMap::new();
+    let mut pending = vec![PROGRAM_START];
+
+    while let Some(addr) = pending.pop() {
+        if code.contains_key(&addr) {
+            continue;
+        }
+        let Some(instruction) = decode(addr) else {
+            continue;
+        };
+        code.insert(addr, instruction);
+
+        let next = addr + instruction.size();
+        match instruction {
+            Instruction::Jump(nnn) => {
+                labels.entry(nnn as usize).or_insert("loc");
+                pending.push(nnn as usize);
+            }
+            Instruction::Call(nnn) => {
+                labels.insert(nnn as usize, "sub");
+                pending.push(nnn as usize);
+                pending.push(next);
+            }
+            Instruction::SkipEqByte(..)
+            | Instruction::SkipNeByte(..)
+            | Instruction::SkipEqReg(..)
+            | Instruction::SkipNeReg(..)
+            | Instruction::SkipKey(_)
+            | Instruction::SkipNotKey(_) => {
+                pending.push(next);
+                let skipped = decode(next).map_or(2, |skipped| skipped.size());
+                pending.push(next + skipped);
+            }
+            Instruction::Ret | Instruction::Exit | Instruction::JumpOffset(_) => (),
+            _ => pending.push(next),
+        }
+    }
+
+    let label = |addr: usize| {
+        labels
+            .get(&addr)
+            .map(|kind| format!("{}_{:03X}", kind, addr))
+    };
+
+    let end = PROGRAM_START + rom.len();
+    let mut lines = Vec::new();
+    let mut addr = PROGRAM_START;
+    while addr < end {
+        if let Some(&instruction) = code.get(&addr) {
+            let size = instruction.size();
+            lines.push(Line {
+                addr: addr as u16,
+                label: label(addr),
+                bytes: rom[addr - PROGRAM_START..addr - PROGRAM_START + size].to_vec(),
+                instruction: Some(instruction),
+            });
+            addr += size;
+            continue;
+        }
+
+        // Data runs until the next instruction or label, whichever is first.
+        let mut len = 1;
+        while len < DATA_BYTES_PER_LINE
+            && addr + len < end
+            && !code.contains_key(&(addr + len))
+            && !labels.contains_key(&(addr + len))
+        {
+            len += 1;
+        }
+        lines.push(Line {
+            addr: addr as u16,
+            label: label(addr),
+            bytes: rom[addr - PROGRAM_START..addr - PROGRAM_START + len].to_vec(),
+            instruction: None,
+        });
+        addr += len;
+    }
+
+    Listing { lines }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(label) = &self.label {
+            writeln!(f, "{}:", label)?;
+        }
+
+        let raw: String = self
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        write!(f, "{:04X}  {:<8}  ", self.addr, raw)?;
+
+        match &self.instruction {
+            Some(instruction) => write!(f, "{}", instruction),
+            None => {
+                let bytes: Vec<String> = self
+                    .bytes
+                    .iter()
+                    .map(|byte| format!("{:#04X}", byte))
+                    .collect();
+                write!(f, "DB {}", bytes.join(", "))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use crate::cpu::{Chip8, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH};
+
+impl Chip8 {
+    /// Width and height of the active display mode, in pixels.
+    pub fn display_size(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_WIDTH, HIRES_HEIGHT)
+        } else {
+            (DISPLAY_WIDTH, DISPLAY_HEIGHT)
+        }
+    }
+
+    /// Whether the pixel at (`x`, `y`) of the active display mode is lit in any plane.
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        self.display[y][x] != 0
+    }
+
+    /// The bitplanes lit at (`x`, `y`): bit 0 for the first plane, bit 1 for
+    /// the second. Anything but XO-CHIP only ever uses the first.
+    pub fn pixel_planes(&self, x: usize, y: usize) -> u8 {
+        self.display[y][x]
+    }
+
+    // Clears the selected bitplanes.
+    pub(crate) fn clear_display(&mut self) {
+        let mask = !self.planes;
+        for row in self.display.iter_mut() {
+            for pixel in row.iter_mut() {
+                *pixel &= mask;
+            }
+        }
+        self.redraw = true;
+    }
+
+    // Moves the selected bitplanes by (dx, dy), filling the gap with unlit pixels.
+    pub(crate) fn scroll(&mut self, dx: isize, dy: isize) {
+        let (width, height) = self.display_size();
+        let planes = self.planes;
+        let before = self.display;
+
+        for row in 0..height {
+            for col in 0..width {
+                let src_row = row as isize - dy;
+                let src_col = col as isize - dx;
+                let inside = (0..height as isize).contains(&src_row)
+                    && (0..width as isize).contains(&src_col);
+
+                let moved = if inside {
+                    before[src_row as usize][src_col as usize] & planes
+                } else {
+                    0
+                };
+                self.display[row][col] = (before[row][col] & !planes) | moved;
+            }
+        }
+        self.redraw = true;
+    }
+
+    // XORs a `width` x `rows` sprite read from I onto the display at (vx, vy)
+    // and sets VF if any lit pixel was turned off. With both XO-CHIP planes
+    // selected, the data for the second plane follows the first. The caller
+    // checks that the sprite lies inside memory.
+    pub(crate) fn draw_sprite(&mut self, vx: u8, vy: u8, width: usize, rows: usize) {
+        let (display_width, display_height) = self.display_size();
+        let bytes_per_row = width / 8;
+
+        // The starting position always wraps, the sprite itself is
+        // clipped or wrapped depending on Quirks::clip_sprites.
+        let vx = vx as usize % display_width;
+        let vy = vy as usize % display_height;
+
+        self.V[0xF] = 0;
+        let mut addr = self.I as usize;
+        for plane in [0b01, 0b10] {
+            if self.planes & plane == 0 {
+                continue;
+            }
+
+            for row in 0..rows {
+                let mut bits = 0u16;
+                for byte in &self.memory[addr..addr + bytes_per_row] {
+                    bits = bits << 8 | *byte as u16;
+                }
+                addr += bytes_per_row;
+
+                for col in 0..width {
+                    if bits & (1 << (width - 1 - col)) == 0 {
+                        continue;
+                    }
+
+                    let (px, py) = (vx + col, vy + row);
+                    if self.quirks.clip_sprites && (px >= display_width || py >= display_height) {
+                        continue;
+                    }
+                    let px = px % display_width;
+                    let py = py % display_height;
+
+                    if self.display[py][px] & plane != 0 {
+                        self.V[0xF] = 1;
+                    }
+                    self.display[py][px] ^= plane;
+                }
+            }
+        }
+        self.redraw = true;
+    }
+}
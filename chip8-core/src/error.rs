@@ -0,0 +1,75 @@
+use std::fmt;
+
+/// A fault raised while executing an instruction.
+///
+/// Every variant carries the address of the faulting instruction and its
+/// opcode. When the fault happened while fetching, `opcode` is 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    UnknownOpcode { pc: u16, opcode: u16 },
+    StackOverflow { pc: u16, opcode: u16 },
+    StackUnderflow { pc: u16, opcode: u16 },
+    MemoryOutOfBounds { pc: u16, opcode: u16, addr: usize },
+    InvalidKey { pc: u16, opcode: u16, key: u8 },
+}
+
+impl Chip8Error {
+    pub fn pc(&self) -> u16 {
+        match *self {
+            Chip8Error::UnknownOpcode { pc, .. }
+            | Chip8Error::StackOverflow { pc, .. }
+            | Chip8Error::StackUnderflow { pc, .. }
+            | Chip8Error::MemoryOutOfBounds { pc, .. }
+            | Chip8Error::InvalidKey { pc, .. } => pc,
+        }
+    }
+
+    pub fn opcode(&self) -> u16 {
+        match *self {
+            Chip8Error::UnknownOpcode { opcode, .. }
+            | Chip8Error::StackOverflow { opcode, .. }
+            | Chip8Error::StackUnderflow { opcode, .. }
+            | Chip8Error::MemoryOutOfBounds { opcode, .. }
+            | Chip8Error::InvalidKey { opcode, .. } => opcode,
+        }
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Chip8Error::UnknownOpcode { .. } => write!(f, "unknown opcode")?,
+            Chip8Error::StackOverflow { .. } => write!(f, "stack overflow")?,
+            Chip8Error::StackUnderflow { .. } => write!(f, "stack underflow")?,
+            Chip8Error::MemoryOutOfBounds { addr, .. } => {
+                write!(f, "memory access out of bounds at {:#05X}", addr)?
+            }
+            Chip8Error::InvalidKey { key, .. } => write!(f, "invalid key {:#04X}", key)?,
+        }
+        write!(f, " ({:04X} at {:#05X})", self.opcode(), self.pc())
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+/// Returned by [`Chip8::load_rom`](crate::Chip8::load_rom) when a ROM does not fit in memory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RomError {
+    TooLarge { size: usize, max: usize },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::TooLarge { size, max } => {
+                write!(
+                    f,
+                    "ROM is {} bytes, at most {} bytes fit in memory",
+                    size, max
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
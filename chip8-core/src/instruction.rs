@@ -0,0 +1,348 @@
+use std::fmt;
+
+use crate::cpu::Chip8;
+use crate::platform::Platform;
+
+/// A decoded instruction. `x` and `y` are register indexes, `nnn` addresses,
+/// `kk` byte immediates and `n` nibble immediates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// `00E0` Clear the display.
+    Cls,
+    /// `00EE` Return from a subroutine.
+    Ret,
+    /// `00Cn` Scroll the display down n lines. (SUPER-CHIP)
+    ScrollDown(u8),
+    /// `00Dn` Scroll the display up n lines. (XO-CHIP)
+    ScrollUp(u8),
+    /// `00FB` Scroll the display right 4 pixels. (SUPER-CHIP)
+    ScrollRight,
+    /// `00FC` Scroll the display left 4 pixels. (SUPER-CHIP)
+    ScrollLeft,
+    /// `00FD` Exit the interpreter. (SUPER-CHIP)
+    Exit,
+    /// `00FE` Switch to lo-res mode. (SUPER-CHIP)
+    Low,
+    /// `00FF` Switch to hi-res mode. (SUPER-CHIP)
+    High,
+    /// `1nnn` Jump to location nnn.
+    Jump(u16),
+    /// `2nnn` Call subroutine at nnn.
+    Call(u16),
+    /// `3xkk` Skip next instruction if Vx = kk.
+    SkipEqByte(u8, u8),
+    /// `4xkk` Skip next instruction if Vx != kk.
+    SkipNeByte(u8, u8),
+    /// `5xy0` Skip next instruction if Vx = Vy.
+    SkipEqReg(u8, u8),
+    /// `5xy2` Store Vx through Vy in memory starting at I. (XO-CHIP)
+    SaveRange(u8, u8),
+    /// `5xy3` Read Vx through Vy from memory starting at I. (XO-CHIP)
+    LoadRange(u8, u8),
+    /// `6xkk` Set Vx = kk.
+    LoadByte(u8, u8),
+    /// `7xkk` Set Vx = Vx + kk.
+    AddByte(u8, u8),
+    /// `8xy0` Set Vx = Vy.
+    LoadReg(u8, u8),
+    /// `8xy1` Set Vx = Vx OR Vy.
+    Or(u8, u8),
+    /// `8xy2` Set Vx = Vx AND Vy.
+    And(u8, u8),
+    /// `8xy3` Set Vx = Vx XOR Vy.
+    Xor(u8, u8),
+    /// `8xy4` Set Vx = Vx + Vy, set VF = carry.
+    AddReg(u8, u8),
+    /// `8xy5` Set Vx = Vx - Vy, set VF = NOT borrow.
+    Sub(u8, u8),
+    /// `8xy6` Set Vx = Vx SHR 1.
+    ShiftRight(u8, u8),
+    /// `8xy7` Set Vx = Vy - Vx, set VF = NOT borrow.
+    SubReverse(u8, u8),
+    /// `8xyE` Set Vx = Vx SHL 1.
+    ShiftLeft(u8, u8),
+    /// `9xy0` Skip next instruction if Vx != Vy.
+    SkipNeReg(u8, u8),
+    /// `Annn` Set I = nnn.
+    LoadIndex(u16),
+    /// `Bnnn` Jump to location nnn + V0.
+    JumpOffset(u16),
+    /// `Cxkk` Set Vx = random byte AND kk.
+    Random(u8, u8),
+    /// `Dxyn` Display n-byte sprite at (Vx, Vy), set VF = collision.
+    Draw(u8, u8, u8),
+    /// `Ex9E` Skip next instruction if key Vx is pressed.
+    SkipKey(u8),
+    /// `ExA1` Skip next instruction if key Vx is not pressed.
+    SkipNotKey(u8),
+    /// `F000 nnnn` Set I = nnnn. (XO-CHIP)
+    LoadLongIndex(u16),
+    /// `Fn01` Select bitplanes n. (XO-CHIP)
+    Plane(u8),
+    /// `F002` Load the audio pattern at I. (XO-CHIP)
+    Audio,
+    /// `Fx07` Set Vx = delay timer value.
+    LoadDelay(u8),
+    /// `Fx0A` Wait for a key press, store the value of the key in Vx.
+    WaitKey(u8),
+    /// `Fx15` Set delay timer = Vx.
+    SetDelay(u8),
+    /// `Fx18` Set sound timer = Vx.
+    SetSound(u8),
+    /// `Fx1E` Set I = I + Vx.
+    AddIndex(u8),
+    /// `Fx29` Set I = location of sprite for digit Vx.
+    LoadFont(u8),
+    /// `Fx30` Set I = location of the big sprite for digit Vx. (SUPER-CHIP)
+    LoadBigFont(u8),
+    /// `Fx33` Store BCD representation of Vx at I, I+1 and I+2.
+    StoreBcd(u8),
+    /// `Fx3A` Set the audio pattern pitch = Vx. (XO-CHIP)
+    Pitch(u8),
+    /// `Fx55` Store V0 through Vx in memory starting at I.
+    StoreRegs(u8),
+    /// `Fx65` Read V0 through Vx from memory starting at I.
+    LoadRegs(u8),
+    /// `Fx75` Store V0 through Vx in the RPL user flags. (SUPER-CHIP)
+    StoreFlags(u8),
+    /// `Fx85` Read V0 through Vx from the RPL user flags. (SUPER-CHIP)
+    LoadFlags(u8),
+}
+
+impl Instruction {
+    /// Decodes `opcode` for `platform`. `next` is the word after it, only
+    /// read by the 4-byte XO-CHIP `F000 nnnn`. Returns `None` for opcodes the
+    /// platform does not have.
+    pub fn decode(opcode: u16, next: u16, platform: Platform) -> Option<Instruction> {
+        use Instruction::*;
+
+        let x = ((opcode & 0x0F00) >> 8) as u8;
+        let y = ((opcode & 0x00F0) >> 4) as u8;
+        let n = (opcode & 0x000F) as u8;
+        let kk = (opcode & 0x00FF) as u8;
+        let nnn = opcode & 0x0FFF;
+
+        let schip = platform.has_schip();
+        let xo = platform.has_xo();
+
+        let instruction = match opcode & 0xF000 {
+            0x0000 => match opcode {
+                0x00E0 => Cls,
+                0x00EE => Ret,
+                0x00C0..=0x00CF if schip => ScrollDown(n),
+                0x00D0..=0x00DF if xo => ScrollUp(n),
+                0x00FB if schip => ScrollRight,
+                0x00FC if schip => ScrollLeft,
+                0x00FD if schip => Exit,
+                0x00FE if schip => Low,
+                0x00FF if schip => High,
+                _ => return None,
+            },
+            0x1000 => Jump(nnn),
+            0x2000 => Call(nnn),
+            0x3000 => SkipEqByte(x, kk),
+            0x4000 => SkipNeByte(x, kk),
+            0x5000 => match n {
+                0x0 => SkipEqReg(x, y),
+                0x2 if xo => SaveRange(x, y),
+                0x3 if xo => LoadRange(x, y),
+                _ => return None,
+            },
+            0x6000 => LoadByte(x, kk),
+            0x7000 => AddByte(x, kk),
+            0x8000 => match n {
+                0x0 => LoadReg(x, y),
+                0x1 => Or(x, y),
+                0x2 => And(x, y),
+                0x3 => Xor(x, y),
+                0x4 => AddReg(x, y),
+                0x5 => Sub(x, y),
+                0x6 => ShiftRight(x, y),
+                0x7 => SubReverse(x, y),
+                0xE => ShiftLeft(x, y),
+                _ => return None,
+            },
+            0x9000 if n == 0 => SkipNeReg(x, y),
+            0xA000 => LoadIndex(nnn),
+            0xB000 => JumpOffset(nnn),
+            0xC000 => Random(x, kk),
+            0xD000 => Draw(x, y, n),
+            0xE000 => match kk {
+                0x9E => SkipKey(x),
+                0xA1 => SkipNotKey(x),
+                _ => return None,
+            },
+            0xF000 => match kk {
+                0x00 if x == 0 && xo => LoadLongIndex(next),
+                0x01 if xo => Plane(x),
+                0x02 if x == 0 && xo => Audio,
+                0x07 => LoadDelay(x),
+                0x0A => WaitKey(x),
+                0x15 => SetDelay(x),
+                0x18 => SetSound(x),
+                0x1E => AddIndex(x),
+                0x29 => LoadFont(x),
+                0x30 if schip => LoadBigFont(x),
+                0x33 => StoreBcd(x),
+                0x3A if xo => Pitch(x),
+                0x55 => StoreRegs(x),
+                0x65 => LoadRegs(x),
+                0x75 if schip => StoreFlags(x),
+                0x85 if schip => LoadFlags(x),
+                _ => return None,
+            },
+            _ => return None,
+        };
+
+        Some(instruction)
+    }
+
+    /// Encodes the instruction as the bytes [`Instruction::decode`] reads
+    /// back. Fields wider than the opcode allows are truncated.
+    pub fn encode(&self) -> Vec<u8> {
+        use Instruction::*;
+
+        let xy = |base: u16, x: u8, y: u8| base | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4;
+        let xkk = |base: u16, x: u8, kk: u8| base | (x as u16 & 0xF) << 8 | kk as u16;
+
+        let opcode = match *self {
+            Cls => 0x00E0,
+            Ret => 0x00EE,
+            ScrollDown(n) => 0x00C0 | (n as u16 & 0xF),
+            ScrollUp(n) => 0x00D0 | (n as u16 & 0xF),
+            ScrollRight => 0x00FB,
+            ScrollLeft => 0x00FC,
+            Exit => 0x00FD,
+            Low => 0x00FE,
+            High => 0x00FF,
+            Jump(nnn) => 0x1000 | (nnn & 0x0FFF),
+            Call(nnn) => 0x2000 | (nnn & 0x0FFF),
+            SkipEqByte(x, kk) => xkk(0x3000, x, kk),
+            SkipNeByte(x, kk) => xkk(0x4000, x, kk),
+            SkipEqReg(x, y) => xy(0x5000, x, y),
+            SaveRange(x, y) => xy(0x5002, x, y),
+            LoadRange(x, y) => xy(0x5003, x, y),
+            LoadByte(x, kk) => xkk(0x6000, x, kk),
+            AddByte(x, kk) => xkk(0x7000, x, kk),
+            LoadReg(x, y) => xy(0x8000, x, y),
+            Or(x, y) => xy(0x8001, x, y),
+            And(x, y) => xy(0x8002, x, y),
+            Xor(x, y) => xy(0x8003, x, y),
+            AddReg(x, y) => xy(0x8004, x, y),
+            Sub(x, y) => xy(0x8005, x, y),
+            ShiftRight(x, y) => xy(0x8006, x, y),
+            SubReverse(x, y) => xy(0x8007, x, y),
+            ShiftLeft(x, y) => xy(0x800E, x, y),
+            SkipNeReg(x, y) => xy(0x9000, x, y),
+            LoadIndex(nnn) => 0xA000 | (nnn & 0x0FFF),
+            JumpOffset(nnn) => 0xB000 | (nnn & 0x0FFF),
+            Random(x, kk) => xkk(0xC000, x, kk),
+            Draw(x, y, n) => xy(0xD000, x, y) | (n as u16 & 0xF),
+            SkipKey(x) => xkk(0xE09E, x, 0),
+            SkipNotKey(x) => xkk(0xE0A1, x, 0),
+            LoadLongIndex(_) => 0xF000,
+            Plane(n) => xkk(0xF001, n, 0),
+            Audio => 0xF002,
+            LoadDelay(x) => xkk(0xF007, x, 0),
+            WaitKey(x) => xkk(0xF00A, x, 0),
+            SetDelay(x) => xkk(0xF015, x, 0),
+            SetSound(x) => xkk(0xF018, x, 0),
+            AddIndex(x) => xkk(0xF01E, x, 0),
+            LoadFont(x) => xkk(0xF029, x, 0),
+            LoadBigFont(x) => xkk(0xF030, x, 0),
+            StoreBcd(x) => xkk(0xF033, x, 0),
+            Pitch(x) => xkk(0xF03A, x, 0),
+            StoreRegs(x) => xkk(0xF055, x, 0),
+            LoadRegs(x) => xkk(0xF065, x, 0),
+            StoreFlags(x) => xkk(0xF075, x, 0),
+            LoadFlags(x) => xkk(0xF085, x, 0),
+        };
+
+        let mut bytes = opcode.to_be_bytes().to_vec();
+        if let LoadLongIndex(nnnn) = *self {
+            bytes.extend_from_slice(&nnnn.to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Size of the encoded instruction in bytes.
+    pub fn size(&self) -> usize {
+        match self {
+            Instruction::LoadLongIndex(_) => 4,
+            _ => 2,
+        }
+    }
+}
+
+impl Chip8 {
+    /// Decodes the instruction at `addr` without executing it.
+    pub fn instruction_at(&self, addr: u16) -> Option<Instruction> {
+        let word = |addr: usize| -> Option<u16> {
+            let bytes = self.memory.get(addr..addr + 2)?;
+            Some(u16::from(bytes[0]) << 8 | u16::from(bytes[1]))
+        };
+
+        let addr = addr as usize;
+        let opcode = word(addr)?;
+        Instruction::decode(opcode, word(addr + 2).unwrap_or(0), self.platform)
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Instruction::*;
+
+        match *self {
+            Cls => write!(f, "CLS"),
+            Ret => write!(f, "RET"),
+            ScrollDown(n) => write!(f, "SCD {}", n),
+            ScrollUp(n) => write!(f, "SCU {}", n),
+            ScrollRight => write!(f, "SCR"),
+            ScrollLeft => write!(f, "SCL"),
+            Exit => write!(f, "EXIT"),
+            Low => write!(f, "LOW"),
+            High => write!(f, "HIGH"),
+            Jump(nnn) => write!(f, "JP {:#05X}", nnn),
+            Call(nnn) => write!(f, "CALL {:#05X}", nnn),
+            SkipEqByte(x, kk) => write!(f, "SE V{:X}, {:#04X}", x, kk),
+            SkipNeByte(x, kk) => write!(f, "SNE V{:X}, {:#04X}", x, kk),
+            SkipEqReg(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            SaveRange(x, y) => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            LoadRange(x, y) => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            LoadByte(x, kk) => write!(f, "LD V{:X}, {:#04X}", x, kk),
+            AddByte(x, kk) => write!(f, "ADD V{:X}, {:#04X}", x, kk),
+            LoadReg(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            Or(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            And(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            Xor(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            AddReg(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Sub(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            ShiftRight(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            SubReverse(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            ShiftLeft(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            SkipNeReg(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            LoadIndex(nnn) => write!(f, "LD I, {:#05X}", nnn),
+            JumpOffset(nnn) => write!(f, "JP V0, {:#05X}", nnn),
+            Random(x, kk) => write!(f, "RND V{:X}, {:#04X}", x, kk),
+            Draw(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            SkipKey(x) => write!(f, "SKP V{:X}", x),
+            SkipNotKey(x) => write!(f, "SKNP V{:X}", x),
+            LoadLongIndex(nnnn) => write!(f, "LD I, LONG {:#06X}", nnnn),
+            Plane(n) => write!(f, "PLANE {}", n),
+            Audio => write!(f, "AUDIO"),
+            LoadDelay(x) => write!(f, "LD V{:X}, DT", x),
+            WaitKey(x) => write!(f, "LD V{:X}, K", x),
+            SetDelay(x) => write!(f, "LD DT, V{:X}", x),
+            SetSound(x) => write!(f, "LD ST, V{:X}", x),
+            AddIndex(x) => write!(f, "ADD I, V{:X}", x),
+            LoadFont(x) => write!(f, "LD F, V{:X}", x),
+            LoadBigFont(x) => write!(f, "LD HF, V{:X}", x),
+            StoreBcd(x) => write!(f, "LD B, V{:X}", x),
+            Pitch(x) => write!(f, "PITCH V{:X}", x),
+            StoreRegs(x) => write!(f, "LD [I], V{:X}", x),
+            LoadRegs(x) => write!(f, "LD V{:X}, [I]", x),
+            StoreFlags(x) => write!(f, "LD R, V{:X}", x),
+            LoadFlags(x) => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
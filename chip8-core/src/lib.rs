@@ -0,0 +1,53 @@
+//! A Bevy-independent CHIP-8 interpreter.
+//!
+//! [`Chip8`] holds the whole machine state and can be driven from any host:
+//! load a ROM with [`Chip8::load_rom`], call [`Chip8::step`] to execute one
+//! instruction and let [`Timers`] count the delay and sound timers down at
+//! 60Hz. Faults are reported as [`Chip8Error`] instead of being printed.
+//! [`disassemble`] turns a ROM into a listing without running it and
+//! [`assemble`] goes the other way.
+
+mod assembler;
+mod audio;
+mod breakpoints;
+mod clock;
+mod cpu;
+mod disasm;
+mod display;
+mod error;
+mod font;
+mod instruction;
+mod movie;
+mod platform;
+mod quirks;
+mod random;
+mod rewind;
+mod savestate;
+mod sha1;
+mod timers;
+
+pub use assembler::{assemble, AsmError};
+pub use audio::{
+    pattern_rate, write_wav, BeeperConfig, PatternWave, Speaker, SquareWave, ToneRecorder,
+    DEFAULT_PITCH, DEFAULT_PITCH_HZ, DEFAULT_VOLUME, SAMPLE_RATE,
+};
+pub use breakpoints::{
+    Access, BreakReason, Breakpoint, Breakpoints, Compare, InvalidBreakpoint, Register,
+};
+pub use clock::{Clock, DEFAULT_CPU_HZ};
+pub use cpu::{
+    Chip8, KeyWait, DISPLAY_HEIGHT, DISPLAY_WIDTH, HIRES_HEIGHT, HIRES_WIDTH, MEMORY_SIZE,
+    PROGRAM_START, XO_MEMORY_SIZE,
+};
+pub use disasm::{disassemble, Line, Listing};
+pub use error::{Chip8Error, RomError};
+pub use font::{BIG_FONTSET, BIG_FONT_START, FONTSET, FONT_START};
+pub use instruction::Instruction;
+pub use movie::{Frame, Movie, MovieError, MOVIE_VERSION};
+pub use platform::{Platform, UnknownPlatform};
+pub use quirks::{IndexIncrement, Quirks, UnknownPreset};
+pub use random::{RandomKind, RandomSource, Sequence, UnknownRandomKind, VipRandom, Xorshift};
+pub use rewind::{Rewind, DEFAULT_REWIND_FRAMES};
+pub use savestate::{StateError, SAVE_STATE_VERSION};
+pub use sha1::sha1;
+pub use timers::{Timers, TIMER_HZ};
@@ -0,0 +1,275 @@
+use std::fmt;
+
+use crate::cpu::Chip8;
+use crate::error::Chip8Error;
+use crate::platform::Platform;
+use crate::quirks::Quirks;
+use crate::random::RandomKind;
+use crate::savestate::{read_platform, write_platform, Reader, StateError};
+
+const MAGIC: &[u8; 4] = b"C8MV";
+
+/// Version written by [`Movie::to_bytes`].
+pub const MOVIE_VERSION: u16 = 3;
+
+/// Returned when a movie file cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MovieError {
+    /// The data is not a movie at all.
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// The movie was recorded while running a different ROM.
+    WrongRom,
+    Truncated,
+    /// A field holds a value no recording could contain.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for MovieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MovieError::BadMagic => write!(f, "not a movie"),
+            MovieError::UnsupportedVersion(version) => write!(
+                f,
+                "movie version {} is not supported (expected {})",
+                version, MOVIE_VERSION
+            ),
+            MovieError::WrongRom => write!(f, "movie was recorded with a different ROM"),
+            MovieError::Truncated => write!(f, "movie is truncated"),
+            MovieError::Invalid(field) => write!(f, "movie has an invalid {}", field),
+        }
+    }
+}
+
+impl std::error::Error for MovieError {}
+
+impl From<StateError> for MovieError {
+    fn from(err: StateError) -> Self {
+        match err {
+            StateError::BadMagic => MovieError::BadMagic,
+            StateError::UnsupportedVersion(version) => MovieError::UnsupportedVersion(version),
+            StateError::WrongRom => MovieError::WrongRom,
+            StateError::Truncated => MovieError::Truncated,
+            StateError::Invalid(field) => MovieError::Invalid(field),
+        }
+    }
+}
+
+/// One recorded frame: the keys held and how far the machine advanced.
+///
+/// Steps and timer ticks are stored rather than recomputed because the
+/// host decides them from wall-clock time, which differs between runs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Frame {
+    /// Bit `n` is set while key `n` is held.
+    pub keys: u16,
+    /// Instructions executed.
+    pub steps: u32,
+    /// 60Hz timer ticks applied after the instructions.
+    pub ticks: u8,
+}
+
+impl Frame {
+    /// Returns the keys currently held on `chip8`'s keypad as a bitmask.
+    pub fn keys_of(chip8: &Chip8) -> u16 {
+        chip8
+            .keypad
+            .iter()
+            .enumerate()
+            .filter(|(_, &key)| key != 0)
+            .fold(0, |keys, (i, _)| keys | 1 << i)
+    }
+
+    /// Presses exactly the keys of this frame on `chip8`'s keypad.
+    pub fn apply_keys(&self, chip8: &mut Chip8) {
+        for (i, key) in chip8.keypad.iter_mut().enumerate() {
+            *key = (self.keys >> i & 1) as u8;
+        }
+    }
+
+    /// Replays this frame: sets the keys, runs the instructions and ticks
+    /// the timers. Stops early on a fault or once the program has exited.
+    pub fn play(&self, chip8: &mut Chip8) -> Result<(), Chip8Error> {
+        self.apply_keys(chip8);
+        for _ in 0..self.steps {
+            chip8.step()?;
+            if chip8.exited {
+                return Ok(());
+            }
+        }
+        chip8.tick_timers_n(self.ticks as u32);
+        Ok(())
+    }
+}
+
+/// A recording of every frame of input from power-on, enough to reproduce
+/// a run exactly.
+#[derive(Debug, Clone)]
+pub struct Movie {
+    /// [`sha1`](crate::sha1) of the ROM the movie was recorded with.
+    pub rom_hash: [u8; 20],
+    pub platform: Platform,
+    pub quirks: Quirks,
+    /// The random source behind `Cxkk` and the seed it started from.
+    pub random: RandomKind,
+    pub seed: u64,
+    pub frames: Vec<Frame>,
+}
+
+impl Movie {
+    /// Starts an empty recording for `chip8`, which should have just been
+    /// created with a `random` source started from `seed`.
+    pub fn new(chip8: &Chip8, rom_hash: [u8; 20], random: RandomKind, seed: u64) -> Self {
+        Self {
+            rom_hash,
+            platform: chip8.platform,
+            quirks: chip8.quirks,
+            random,
+            seed,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Creates the machine the recording started from. The ROM still has to
+    /// be loaded into it.
+    pub fn machine(&self) -> Chip8 {
+        let mut chip8 = Chip8::with_platform(self.platform);
+        chip8.quirks = self.quirks;
+        chip8.random = self.random.source(self.seed);
+        chip8
+    }
+
+    /// Serializes the movie.
+    ///
+    /// The layout is a `C8MV` magic, the version as a big-endian `u16`, the
+    /// ROM hash, the platform and quirks, the random source and its seed and
+    /// the frame count, followed by 7 bytes per frame.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(48 + self.frames.len() * 7);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&MOVIE_VERSION.to_be_bytes());
+        out.extend_from_slice(&self.rom_hash);
+        write_platform(&mut out, self.platform, &self.quirks);
+        let random = RandomKind::ALL
+            .iter()
+            .position(|&other| other == self.random)
+            .expect("every random source is listed");
+        out.push(random as u8);
+        out.extend_from_slice(&self.seed.to_be_bytes());
+        out.extend_from_slice(&(self.frames.len() as u32).to_be_bytes());
+        for frame in &self.frames {
+            out.extend_from_slice(&frame.keys.to_be_bytes());
+            out.extend_from_slice(&frame.steps.to_be_bytes());
+            out.push(frame.ticks);
+        }
+        out
+    }
+
+    /// Reads a movie written by [`Movie::to_bytes`], checking that it was
+    /// recorded with the ROM whose hash is `rom_hash`.
+    pub fn from_bytes(data: &[u8], rom_hash: &[u8; 20]) -> Result<Movie, MovieError> {
+        let mut reader = Reader::new(data);
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(MovieError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != MOVIE_VERSION {
+            return Err(MovieError::UnsupportedVersion(version));
+        }
+        if reader.bytes(20)? != rom_hash {
+            return Err(MovieError::WrongRom);
+        }
+
+        let (platform, quirks) = read_platform(&mut reader)?;
+        let random = match RandomKind::ALL.get(reader.u8()? as usize) {
+            Some(&random) => random,
+            None => return Err(MovieError::Invalid("random source")),
+        };
+        let seed = reader.u64()?;
+        let count = reader.u32()?;
+        let mut frames = Vec::new();
+        for _ in 0..count {
+            frames.push(Frame {
+                keys: reader.u16()?,
+                steps: reader.u32()?,
+                ticks: reader.u8()?,
+            });
+        }
+        if !reader.is_empty() {
+            return Err(MovieError::Invalid("length"));
+        }
+
+        Ok(Movie {
+            rom_hash: *rom_hash,
+            platform,
+            quirks,
+            random,
+            seed,
+            frames,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROM_HASH: [u8; 20] = [3; 20];
+
+    // Counts in V1 while key 0 is up and rolls a random byte into V2.
+    const ROM: [u8; 8] = [0xE0, 0x9E, 0x71, 0x01, 0xC2, 0xFF, 0x12, 0x00];
+
+    #[test]
+    fn written_movie_reads_back_and_replays() {
+        let random = RandomKind::Vip;
+        let seed = 0x1234_5678;
+        let mut chip8 = Chip8::with_platform(Platform::Chip8);
+        chip8.random = random.source(seed);
+        chip8.load_rom(&ROM).unwrap();
+        let mut movie = Movie::new(&chip8, ROM_HASH, random, seed);
+
+        for i in 0..10u32 {
+            chip8.keypad[0] = (i % 3 == 0) as u8;
+            let frame = Frame {
+                keys: Frame::keys_of(&chip8),
+                steps: 5 + i,
+                ticks: 1,
+            };
+            frame.play(&mut chip8).unwrap();
+            movie.frames.push(frame);
+        }
+
+        let read = Movie::from_bytes(&movie.to_bytes(), &ROM_HASH).unwrap();
+        assert_eq!(read.frames, movie.frames);
+        assert_eq!((read.platform, read.quirks), (movie.platform, movie.quirks));
+        assert_eq!((read.random, read.seed), (random, seed));
+
+        let mut replay = read.machine();
+        replay.load_rom(&ROM).unwrap();
+        for frame in &read.frames {
+            frame.play(&mut replay).unwrap();
+        }
+        assert_eq!(replay.V, chip8.V);
+        assert_eq!(replay.pc, chip8.pc);
+    }
+
+    #[test]
+    fn random_source_round_trips() {
+        let chip8 = Chip8::with_platform(Platform::Chip8);
+        for random in RandomKind::ALL {
+            let movie = Movie::new(&chip8, ROM_HASH, random, 99);
+            let read = Movie::from_bytes(&movie.to_bytes(), &ROM_HASH).unwrap();
+            assert_eq!(read.random, random);
+        }
+    }
+
+    #[test]
+    fn other_rom_is_refused() {
+        let chip8 = Chip8::with_platform(Platform::Chip8);
+        let movie = Movie::new(&chip8, ROM_HASH, RandomKind::Xorshift, 1);
+        assert!(matches!(
+            Movie::from_bytes(&movie.to_bytes(), &[4; 20]),
+            Err(MovieError::WrongRom)
+        ));
+    }
+}
@@ -0,0 +1,80 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cpu::{MEMORY_SIZE, XO_MEMORY_SIZE};
+use crate::quirks::Quirks;
+
+/// The instruction set a ROM was written for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    /// The original COSMAC VIP instruction set.
+    #[default]
+    Chip8,
+    /// SUPER-CHIP 1.1: 128x64 hi-res mode, scrolling, big sprites and fonts.
+    SuperChip,
+    /// XO-CHIP: SUPER-CHIP plus 64 KB of memory, two bitplanes and audio patterns.
+    XoChip,
+}
+
+impl Platform {
+    /// Platform names accepted by [`Platform::from_str`].
+    pub const NAMES: [&'static str; 3] = ["chip8", "schip", "xochip"];
+
+    /// Every platform, in the same order as [`Platform::NAMES`].
+    pub const ALL: [Platform; 3] = [Platform::Chip8, Platform::SuperChip, Platform::XoChip];
+
+    pub fn has_schip(self) -> bool {
+        self != Platform::Chip8
+    }
+
+    pub fn has_xo(self) -> bool {
+        self == Platform::XoChip
+    }
+
+    pub fn memory_size(self) -> usize {
+        if self.has_xo() {
+            XO_MEMORY_SIZE
+        } else {
+            MEMORY_SIZE
+        }
+    }
+
+    /// The quirks most ROMs for this platform expect.
+    pub fn default_quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::MODERN,
+            Platform::SuperChip => Quirks::SUPER_CHIP,
+            Platform::XoChip => Quirks::XO_CHIP,
+        }
+    }
+}
+
+/// Returned when parsing an unknown platform name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPlatform(pub String);
+
+impl fmt::Display for UnknownPlatform {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown platform '{}', expected one of: {}",
+            self.0,
+            Platform::NAMES.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownPlatform {}
+
+impl FromStr for Platform {
+    type Err = UnknownPlatform;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Ok(Platform::Chip8),
+            "schip" | "super-chip" | "superchip" => Ok(Platform::SuperChip),
+            "xochip" | "xo-chip" => Ok(Platform::XoChip),
+            _ => Err(UnknownPlatform(name.to_string())),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// What `Fx55`/`Fx65` leave in `I` after storing or loading registers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexIncrement {
+    /// `I` is left unchanged.
+    Unchanged,
+    /// `I` is incremented by `x` (CHIP-48).
+    ByX,
+    /// `I` is incremented by `x + 1` (COSMAC VIP).
+    ByXPlusOne,
+}
+
+/// Behaviour of the instructions that differ between CHIP-8 implementations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift `Vy` into `Vx` instead of shifting `Vx` in place.
+    pub shift_uses_vy: bool,
+    /// `I` after `Fx55`/`Fx65`.
+    pub index_increment: IndexIncrement,
+    /// `Bnnn` jumps to `xnn + Vx` instead of `nnn + V0`.
+    pub jump_uses_vx: bool,
+    /// `Fx1E` sets `VF` when `I` goes past `0xFFF`.
+    pub index_overflow_sets_vf: bool,
+    /// `Dxyn` clips sprites at the screen edge instead of wrapping them around.
+    pub clip_sprites: bool,
+    /// `8xy1`/`8xy2`/`8xy3` reset `VF` to 0.
+    pub logic_resets_vf: bool,
+    /// `Fx0A` waits for the key to be released again before it finishes,
+    /// instead of as soon as one is pressed, so a single press is only
+    /// read once.
+    pub wait_for_release: bool,
+}
+
+impl Quirks {
+    pub const COSMAC_VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        index_increment: IndexIncrement::ByXPlusOne,
+        jump_uses_vx: false,
+        index_overflow_sets_vf: false,
+        clip_sprites: true,
+        logic_resets_vf: true,
+        wait_for_release: true,
+    };
+
+    pub const CHIP_48: Quirks = Quirks {
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::ByX,
+        jump_uses_vx: true,
+        index_overflow_sets_vf: false,
+        clip_sprites: true,
+        logic_resets_vf: false,
+        wait_for_release: false,
+    };
+
+    pub const SUPER_CHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::Unchanged,
+        jump_uses_vx: true,
+        index_overflow_sets_vf: false,
+        clip_sprites: true,
+        logic_resets_vf: false,
+        wait_for_release: false,
+    };
+
+    pub const XO_CHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        index_increment: IndexIncrement::ByXPlusOne,
+        jump_uses_vx: false,
+        index_overflow_sets_vf: false,
+        clip_sprites: false,
+        logic_resets_vf: false,
+        wait_for_release: true,
+    };
+
+    pub const MODERN: Quirks = Quirks {
+        shift_uses_vy: false,
+        index_increment: IndexIncrement::Unchanged,
+        jump_uses_vx: false,
+        index_overflow_sets_vf: false,
+        clip_sprites: false,
+        logic_resets_vf: false,
+        wait_for_release: true,
+    };
+
+    /// Modern behaviour plus the CHIP-8 interpreter for the Amiga's `Fx1E`,
+    /// which sets `VF` when `I` overflows. Spacefight 2091! relies on it.
+    pub const AMIGA: Quirks = Quirks {
+        index_overflow_sets_vf: true,
+        ..Quirks::MODERN
+    };
+
+    /// Preset names accepted by [`Quirks::from_str`].
+    pub const PRESET_NAMES: [&'static str; 6] =
+        ["vip", "chip48", "schip", "xochip", "modern", "amiga"];
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::MODERN
+    }
+}
+
+/// Returned when parsing an unknown quirks preset name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPreset(pub String);
+
+impl fmt::Display for UnknownPreset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown quirks preset '{}', expected one of: {}",
+            self.0,
+            Quirks::PRESET_NAMES.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownPreset {}
+
+impl FromStr for Quirks {
+    type Err = UnknownPreset;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac-vip" | "chip8" => Ok(Quirks::COSMAC_VIP),
+            "chip48" | "chip-48" => Ok(Quirks::CHIP_48),
+            "schip" | "super-chip" | "superchip" => Ok(Quirks::SUPER_CHIP),
+            "xochip" | "xo-chip" => Ok(Quirks::XO_CHIP),
+            "modern" => Ok(Quirks::MODERN),
+            "amiga" | "spacefight" => Ok(Quirks::AMIGA),
+            _ => Err(UnknownPreset(name.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{Chip8, PROGRAM_START};
+    use crate::platform::Platform;
+
+    #[test]
+    fn amiga_sets_vf_when_index_overflows() {
+        let mut chip8 = Chip8::with_platform(Platform::Chip8);
+        chip8.quirks = "amiga".parse().unwrap();
+        chip8.load_rom(&[0xF0, 0x1E, 0xF0, 0x1E]).unwrap();
+        chip8.I = 0xFFE;
+        chip8.V[0] = 1;
+
+        chip8.step().unwrap();
+        assert_eq!((chip8.I, chip8.V[0xF]), (0xFFF, 0));
+        chip8.step().unwrap();
+        assert_eq!((chip8.I, chip8.V[0xF]), (0x1000, 1));
+        assert_eq!(chip8.pc as usize, PROGRAM_START + 4);
+    }
+
+    #[test]
+    fn other_presets_leave_vf_alone() {
+        for name in Quirks::PRESET_NAMES.iter().filter(|&&name| name != "amiga") {
+            let quirks: Quirks = name.parse().unwrap();
+            assert!(!quirks.index_overflow_sets_vf, "{}", name);
+        }
+    }
+}
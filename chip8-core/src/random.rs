@@ -0,0 +1,249 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cpu::PROGRAM_START;
+
+/// Supplies the random bytes `Cxkk` masks with `kk`.
+///
+/// The machine owns its source, so it is cloned, saved and restored along
+/// with everything else. Implement this to feed `Cxkk` a known sequence.
+pub trait RandomSource: Send + Sync {
+    /// Returns the next byte. `memory` is the machine's memory, for sources
+    /// that imitate interpreters which took their numbers from it.
+    fn next_byte(&mut self, memory: &[u8]) -> u8;
+
+    /// Internal state, stored in save states. It should start with a tag
+    /// byte so [`RandomSource::restore`] can refuse another source's state.
+    fn save(&self) -> Vec<u8>;
+
+    /// Restores what [`RandomSource::save`] returned. Returns `false` if
+    /// `state` was not saved by this kind of source.
+    fn restore(&mut self, state: &[u8]) -> bool;
+
+    fn clone_box(&self) -> Box<dyn RandomSource>;
+}
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// The default source: xorshift64*, which is fast and good enough for games.
+#[derive(Debug, Clone)]
+pub struct Xorshift {
+    state: u64,
+}
+
+impl Xorshift {
+    const TAG: u8 = b'X';
+
+    pub fn new(seed: u64) -> Self {
+        // Spread the seed with a SplitMix64 step so nearby seeds give
+        // unrelated sequences and 0 is still a usable seed.
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        Self { state: z.max(1) }
+    }
+}
+
+impl RandomSource for Xorshift {
+    fn next_byte(&mut self, _memory: &[u8]) -> u8 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 56) as u8
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = vec![Self::TAG];
+        state.extend_from_slice(&self.state.to_be_bytes());
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> bool {
+        match state {
+            [Self::TAG, bytes @ ..] if bytes.len() == 8 => {
+                let mut value = [0; 8];
+                value.copy_from_slice(bytes);
+                // xorshift never leaves 0 once it gets there.
+                self.state = u64::from_be_bytes(value).max(1);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+/// Imitates the COSMAC VIP interpreter, which had no real generator: it
+/// walked a pointer through a page of its own code and mixed each byte it
+/// found into the previous result. The first page of the program stands in
+/// for the interpreter's code here, so the numbers are as poor and repetitive
+/// as some VIP games expect.
+#[derive(Debug, Clone)]
+pub struct VipRandom {
+    pointer: u8,
+    last: u8,
+}
+
+impl VipRandom {
+    const TAG: u8 = b'V';
+
+    pub fn new(seed: u64) -> Self {
+        Self {
+            pointer: seed as u8,
+            last: (seed >> 8) as u8,
+        }
+    }
+}
+
+impl RandomSource for VipRandom {
+    fn next_byte(&mut self, memory: &[u8]) -> u8 {
+        self.pointer = self.pointer.wrapping_add(1);
+        let code = memory
+            .get(PROGRAM_START + self.pointer as usize)
+            .copied()
+            .unwrap_or(0);
+        self.last = self.last.wrapping_add(code).wrapping_add(self.pointer);
+        self.last = self.last.rotate_right(1);
+        self.last
+    }
+
+    fn save(&self) -> Vec<u8> {
+        vec![Self::TAG, self.pointer, self.last]
+    }
+
+    fn restore(&mut self, state: &[u8]) -> bool {
+        match *state {
+            [Self::TAG, pointer, last] => {
+                self.pointer = pointer;
+                self.last = last;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+/// Returns the given bytes in order, starting over after the last one.
+/// Meant for tests that need `Cxkk` to produce known values.
+#[derive(Debug, Clone)]
+pub struct Sequence {
+    bytes: Vec<u8>,
+    pos: usize,
+}
+
+impl Sequence {
+    const TAG: u8 = b'S';
+
+    pub fn new(bytes: Vec<u8>) -> Self {
+        assert!(
+            !bytes.is_empty(),
+            "a random sequence needs at least one byte"
+        );
+        Self { bytes, pos: 0 }
+    }
+}
+
+impl RandomSource for Sequence {
+    fn next_byte(&mut self, _memory: &[u8]) -> u8 {
+        let byte = self.bytes[self.pos];
+        self.pos = (self.pos + 1) % self.bytes.len();
+        byte
+    }
+
+    fn save(&self) -> Vec<u8> {
+        let mut state = vec![Self::TAG];
+        state.extend_from_slice(&(self.pos as u32).to_be_bytes());
+        state
+    }
+
+    fn restore(&mut self, state: &[u8]) -> bool {
+        match *state {
+            [Self::TAG, a, b, c, d] => {
+                self.pos = u32::from_be_bytes([a, b, c, d]) as usize % self.bytes.len();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+/// The built-in random sources, selectable by name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RandomKind {
+    /// [`Xorshift`].
+    #[default]
+    Xorshift,
+    /// [`VipRandom`].
+    Vip,
+}
+
+impl RandomKind {
+    /// Names accepted by [`RandomKind::from_str`].
+    pub const NAMES: [&'static str; 2] = ["xorshift", "vip"];
+
+    /// Every kind, in the same order as [`RandomKind::NAMES`].
+    pub const ALL: [RandomKind; 2] = [RandomKind::Xorshift, RandomKind::Vip];
+
+    /// Creates a source of this kind starting from `seed`.
+    pub fn source(self, seed: u64) -> Box<dyn RandomSource> {
+        match self {
+            RandomKind::Xorshift => Box::new(Xorshift::new(seed)),
+            RandomKind::Vip => Box::new(VipRandom::new(seed)),
+        }
+    }
+
+    /// Recreates a built-in source from its saved state.
+    pub(crate) fn restore(state: &[u8]) -> Option<Box<dyn RandomSource>> {
+        Self::ALL.iter().find_map(|kind| {
+            let mut source = kind.source(0);
+            source.restore(state).then_some(source)
+        })
+    }
+}
+
+/// Returned when parsing an unknown random source name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownRandomKind(pub String);
+
+impl fmt::Display for UnknownRandomKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown random source '{}', expected one of: {}",
+            self.0,
+            RandomKind::NAMES.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownRandomKind {}
+
+impl FromStr for RandomKind {
+    type Err = UnknownRandomKind;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "xorshift" => Ok(RandomKind::Xorshift),
+            "vip" | "cosmac" => Ok(RandomKind::Vip),
+            _ => Err(UnknownRandomKind(name.to_string())),
+        }
+    }
+}
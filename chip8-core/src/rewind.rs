@@ -0,0 +1,274 @@
+use std::collections::VecDeque;
+
+use crate::cpu::Chip8;
+use crate::savestate::StateError;
+
+/// Snapshots kept by default: one per frame for 30 seconds.
+pub const DEFAULT_REWIND_FRAMES: usize = 30 * 60;
+
+/// A ring buffer of recent machine states for stepping backwards in time.
+///
+/// Only the newest snapshot is kept whole. Every older one is stored as the
+/// run-length encoded XOR against its successor, which is a few bytes per
+/// frame since most of memory and the display do not change.
+#[derive(Debug, Clone)]
+pub struct Rewind {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+    // deltas[i] turns snapshot i + 1 back into snapshot i.
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Default for Rewind {
+    fn default() -> Self {
+        Self::new(DEFAULT_REWIND_FRAMES)
+    }
+}
+
+impl Rewind {
+    /// Creates a buffer that can step back `capacity` snapshots.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            latest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    /// Records the current state, dropping the oldest one when full.
+    pub fn push(&mut self, chip8: &Chip8) {
+        let state = chip8.state_bytes();
+
+        if let Some(latest) = self.latest.take() {
+            if latest.len() == state.len() {
+                self.deltas.push_back(encode_delta(&latest, &state));
+                if self.deltas.len() > self.capacity {
+                    self.deltas.pop_front();
+                }
+            } else {
+                // The platform changed, older states cannot be reached from here.
+                self.deltas.clear();
+            }
+        }
+        self.latest = Some(state);
+    }
+
+    /// Steps `chip8` back one snapshot. Returns `false` once the oldest one
+    /// has been reached. Like [`Chip8::load_state`], the machine keeps its
+    /// random source and is left untouched on error.
+    pub fn pop(&mut self, chip8: &mut Chip8) -> Result<bool, StateError> {
+        let (Some(latest), Some(delta)) = (self.latest.as_mut(), self.deltas.pop_back()) else {
+            return Ok(false);
+        };
+        apply_delta(latest, &delta);
+        chip8.restore_state_bytes(latest)?;
+        Ok(true)
+    }
+
+    /// Number of snapshots [`Rewind::pop`] can still step back.
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.latest = None;
+        self.deltas.clear();
+    }
+
+    /// Bytes held by the snapshots.
+    pub fn memory_usage(&self) -> usize {
+        self.latest.as_ref().map_or(0, Vec::len) + self.deltas.iter().map(Vec::len).sum::<usize>()
+    }
+}
+
+// XORs `old` and `new` and encodes the result as pairs of (unchanged bytes
+// to skip, changed bytes) with LEB128 lengths.
+fn encode_delta(old: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+
+    while i < new.len() {
+        let start = i;
+        while i < new.len() && old[i] == new[i] {
+            i += 1;
+        }
+        if i == new.len() {
+            break;
+        }
+        let skip = i - start;
+
+        let changed_start = i;
+        while i < new.len() && old[i] != new[i] {
+            i += 1;
+        }
+
+        write_length(&mut out, skip);
+        write_length(&mut out, i - changed_start);
+        out.extend(
+            old[changed_start..i]
+                .iter()
+                .zip(&new[changed_start..i])
+                .map(|(a, b)| a ^ b),
+        );
+    }
+
+    out
+}
+
+fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut i = 0;
+
+    while i < delta.len() {
+        pos += read_length(delta, &mut i);
+        let len = read_length(delta, &mut i);
+        for (byte, xor) in state[pos..pos + len].iter_mut().zip(&delta[i..i + len]) {
+            *byte ^= xor;
+        }
+        pos += len;
+        i += len;
+    }
+}
+
+fn write_length(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_length(data: &[u8], i: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*i];
+        *i += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Platform;
+    use crate::random::Sequence;
+
+    // A machine whose memory and registers differ with `n`.
+    fn machine(platform: Platform, n: u8) -> Chip8 {
+        let mut chip8 = Chip8::with_platform(platform);
+        chip8.V[0] = n;
+        chip8.memory[0x300] = n;
+        chip8
+    }
+
+    // Encodes the step back from `new` to `old` and checks it lands there.
+    fn round_trip(old: &[u8], new: &[u8]) {
+        let delta = encode_delta(old, new);
+        let mut state = new.to_vec();
+        apply_delta(&mut state, &delta);
+        assert_eq!(state, old);
+    }
+
+    #[test]
+    fn identical_frames_need_no_delta() {
+        let state = machine(Platform::Chip8, 1).state_bytes();
+        assert!(encode_delta(&state, &state).is_empty());
+        round_trip(&state, &state);
+    }
+
+    #[test]
+    fn long_runs_round_trip() {
+        let old = vec![0; 1000];
+        let mut new = old.clone();
+        // A 200-byte skip, then 300 changed bytes: both lengths take two
+        // LEB128 bytes.
+        for (i, byte) in new[200..500].iter_mut().enumerate() {
+            *byte = i as u8 | 1;
+        }
+        new[999] = 0xFF;
+        round_trip(&old, &new);
+        round_trip(&new, &old);
+    }
+
+    #[test]
+    fn lengths_round_trip() {
+        for value in [0, 1, 127, 128, 300, 16383, 16384, 1 << 20, usize::MAX] {
+            let mut out = Vec::new();
+            write_length(&mut out, value);
+            let mut i = 0;
+            assert_eq!(read_length(&out, &mut i), value);
+            assert_eq!(i, out.len());
+        }
+    }
+
+    #[test]
+    fn steps_back_through_every_frame() {
+        let mut rewind = Rewind::new(10);
+        for n in 0..5 {
+            rewind.push(&machine(Platform::Chip8, n));
+        }
+
+        let mut chip8 = machine(Platform::Chip8, 4);
+        for n in (0..4).rev() {
+            assert_eq!(rewind.pop(&mut chip8), Ok(true));
+            assert_eq!((chip8.V[0], chip8.memory[0x300]), (n, n));
+        }
+        assert_eq!(rewind.pop(&mut chip8), Ok(false));
+        assert_eq!(chip8.V[0], 0);
+    }
+
+    #[test]
+    fn full_buffer_drops_the_oldest_frames() {
+        let mut rewind = Rewind::new(3);
+        for n in 0..6 {
+            rewind.push(&machine(Platform::Chip8, n));
+        }
+        assert_eq!(rewind.len(), 3);
+
+        let mut chip8 = machine(Platform::Chip8, 5);
+        for n in [4, 3, 2] {
+            assert_eq!(rewind.pop(&mut chip8), Ok(true));
+            assert_eq!(chip8.V[0], n);
+        }
+        assert_eq!(rewind.pop(&mut chip8), Ok(false));
+        assert_eq!(chip8.V[0], 2);
+    }
+
+    #[test]
+    fn platform_change_forgets_older_frames() {
+        let mut rewind = Rewind::new(10);
+        rewind.push(&machine(Platform::Chip8, 1));
+        rewind.push(&machine(Platform::Chip8, 2));
+        rewind.push(&machine(Platform::XoChip, 3));
+        assert!(rewind.is_empty());
+
+        rewind.push(&machine(Platform::XoChip, 4));
+        let mut chip8 = machine(Platform::XoChip, 4);
+        assert_eq!(rewind.pop(&mut chip8), Ok(true));
+        assert_eq!((chip8.platform, chip8.V[0]), (Platform::XoChip, 3));
+        assert_eq!(rewind.pop(&mut chip8), Ok(false));
+    }
+
+    #[test]
+    fn keeps_the_machine_random_source() {
+        let mut chip8 = machine(Platform::Chip8, 0);
+        chip8.random = Box::new(Sequence::new(vec![10, 20, 30]));
+        let mut rewind = Rewind::new(10);
+        rewind.push(&chip8);
+        chip8.random.next_byte(&[]);
+        rewind.push(&chip8);
+
+        assert_eq!(rewind.pop(&mut chip8), Ok(true));
+        assert_eq!(chip8.random.next_byte(&[]), 10);
+        assert_eq!(chip8.random.next_byte(&[]), 20);
+    }
+}
@@ -0,0 +1,428 @@
+use std::fmt;
+
+use crate::cpu::{Chip8, KeyWait, HIRES_HEIGHT, HIRES_WIDTH};
+use crate::platform::Platform;
+use crate::quirks::{IndexIncrement, Quirks};
+use crate::random::{RandomKind, RandomSource};
+
+const MAGIC: &[u8; 4] = b"C8ST";
+
+/// Version written by [`Chip8::save_state`]. Bumped whenever the layout
+/// changes; older states are rejected rather than misread.
+pub const SAVE_STATE_VERSION: u16 = 5;
+
+/// Returned when a save state cannot be restored.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The data is not a save state at all.
+    BadMagic,
+    UnsupportedVersion(u16),
+    /// The state was saved while running a different ROM.
+    WrongRom,
+    Truncated,
+    /// A field holds a value no machine could be in.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => write!(
+                f,
+                "save state version {} is not supported (expected {})",
+                version, SAVE_STATE_VERSION
+            ),
+            StateError::WrongRom => write!(f, "save state belongs to a different ROM"),
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(field) => write!(f, "save state has an invalid {}", field),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl Chip8 {
+    /// Serializes the whole machine. `rom_hash` is the [`sha1`](crate::sha1)
+    /// of the loaded ROM and is checked again by [`Chip8::load_state`].
+    ///
+    /// The layout is a `C8ST` magic, the version as a big-endian `u16` and
+    /// the ROM hash, followed by the machine state.
+    pub fn save_state(&self, rom_hash: &[u8; 20]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + HIRES_WIDTH * HIRES_HEIGHT + 256);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&SAVE_STATE_VERSION.to_be_bytes());
+        out.extend_from_slice(rom_hash);
+        self.write_state(&mut out);
+        out
+    }
+
+    /// Restores a state written by [`Chip8::save_state`] for the same ROM.
+    /// On error the machine is left untouched.
+    pub fn load_state(&mut self, data: &[u8], rom_hash: &[u8; 20]) -> Result<(), StateError> {
+        let mut reader = Reader::new(data);
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version != SAVE_STATE_VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+        if reader.bytes(20)? != rom_hash {
+            return Err(StateError::WrongRom);
+        }
+
+        let state = Chip8::read_state(&mut reader, Some(&*self.random))?;
+        if !reader.is_empty() {
+            return Err(StateError::Invalid("length"));
+        }
+        *self = state;
+        self.redraw = true;
+        Ok(())
+    }
+
+    // The machine state without the header, for snapshots that never leave
+    // the process.
+    pub(crate) fn state_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write_state(&mut out);
+        out
+    }
+
+    // Restores what `state_bytes` returned, keeping the random source like
+    // `load_state` does. On error the machine is left untouched.
+    pub(crate) fn restore_state_bytes(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut reader = Reader::new(data);
+        let state = Chip8::read_state(&mut reader, Some(&*self.random))?;
+        if !reader.is_empty() {
+            return Err(StateError::Invalid("length"));
+        }
+        *self = state;
+        self.redraw = true;
+        Ok(())
+    }
+
+    fn write_state(&self, out: &mut Vec<u8>) {
+        write_platform(out, self.platform, &self.quirks);
+
+        out.extend_from_slice(&self.memory);
+        out.extend_from_slice(&self.opcode.to_be_bytes());
+        out.extend_from_slice(&self.V);
+        out.extend_from_slice(&self.I.to_be_bytes());
+        out.extend_from_slice(&self.pc.to_be_bytes());
+        for row in &self.display {
+            out.extend_from_slice(row);
+        }
+        out.push(self.hires as u8);
+        out.push(self.planes);
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+        for addr in &self.stack {
+            out.extend_from_slice(&addr.to_be_bytes());
+        }
+        out.push(self.sp);
+        out.extend_from_slice(&self.keypad);
+        out.extend_from_slice(&self.rpl);
+        out.push(self.exited as u8);
+        match &self.audio_pattern {
+            Some(pattern) => {
+                out.push(1);
+                out.extend_from_slice(pattern);
+            }
+            None => out.push(0),
+        }
+        out.push(self.pitch);
+        let random = self.random.save();
+        out.extend_from_slice(&(random.len() as u32).to_be_bytes());
+        out.extend_from_slice(&random);
+        match self.key_wait {
+            None => out.push(0),
+            Some(KeyWait { x, key: None, held }) => {
+                out.extend_from_slice(&[1, x]);
+                out.extend_from_slice(&held.to_be_bytes());
+            }
+            Some(KeyWait {
+                x,
+                key: Some(key),
+                held,
+            }) => {
+                out.extend_from_slice(&[2, x, key]);
+                out.extend_from_slice(&held.to_be_bytes());
+            }
+        }
+    }
+
+    // `random` is the source to restore the saved random state into. Saves
+    // made by another kind of source fall back to the built-in one they
+    // came from.
+    fn read_state(
+        reader: &mut Reader,
+        random: Option<&dyn RandomSource>,
+    ) -> Result<Chip8, StateError> {
+        let (platform, quirks) = read_platform(reader)?;
+        let mut chip8 = Chip8::with_platform(platform);
+        chip8.quirks = quirks;
+
+        let memory_size = chip8.memory.len();
+        chip8.memory.copy_from_slice(reader.bytes(memory_size)?);
+        chip8.opcode = reader.u16()?;
+        chip8.V.copy_from_slice(reader.bytes(16)?);
+        chip8.I = reader.u16()?;
+        chip8.pc = reader.u16()?;
+        for row in chip8.display.iter_mut() {
+            row.copy_from_slice(reader.bytes(HIRES_WIDTH)?);
+        }
+        chip8.hires = reader.bool()?;
+        chip8.planes = reader.u8()?;
+        if chip8.planes > 0b11 {
+            return Err(StateError::Invalid("bitplane selection"));
+        }
+        chip8.delay_timer = reader.u8()?;
+        chip8.sound_timer = reader.u8()?;
+        for addr in chip8.stack.iter_mut() {
+            *addr = reader.u16()?;
+        }
+        chip8.sp = reader.u8()?;
+        if chip8.sp as usize > chip8.stack.len() {
+            return Err(StateError::Invalid("stack pointer"));
+        }
+        chip8.keypad.copy_from_slice(reader.bytes(16)?);
+        chip8.rpl.copy_from_slice(reader.bytes(16)?);
+        chip8.exited = reader.bool()?;
+        chip8.audio_pattern = match reader.u8()? {
+            0 => None,
+            1 => {
+                let mut pattern = [0; 16];
+                pattern.copy_from_slice(reader.bytes(16)?);
+                Some(pattern)
+            }
+            _ => return Err(StateError::Invalid("audio pattern")),
+        };
+        chip8.pitch = reader.u8()?;
+        let len = reader.u32()? as usize;
+        let state = reader.bytes(len)?;
+        chip8.random = restore_random(random, state)?;
+        chip8.key_wait = match reader.u8()? {
+            0 => None,
+            1 => Some(KeyWait {
+                x: reader.u8()?,
+                key: None,
+                held: reader.u16()?,
+            }),
+            2 => Some(KeyWait {
+                x: reader.u8()?,
+                key: Some(reader.u8()?),
+                held: reader.u16()?,
+            }),
+            _ => return Err(StateError::Invalid("key wait")),
+        };
+        if let Some(wait) = chip8.key_wait {
+            if wait.x > 0xF || wait.key.is_some_and(|key| key > 0xF) {
+                return Err(StateError::Invalid("key wait"));
+            }
+        }
+
+        Ok(chip8)
+    }
+}
+
+fn restore_random(
+    current: Option<&dyn RandomSource>,
+    state: &[u8],
+) -> Result<Box<dyn RandomSource>, StateError> {
+    if let Some(current) = current {
+        let mut random = current.clone_box();
+        if random.restore(state) {
+            return Ok(random);
+        }
+    }
+    RandomKind::restore(state).ok_or(StateError::Invalid("random state"))
+}
+
+// The platform and its quirks, shared with movies which start from a fresh
+// machine instead of a full state.
+pub(crate) fn write_platform(out: &mut Vec<u8>, platform: Platform, quirks: &Quirks) {
+    let platform = Platform::ALL
+        .iter()
+        .position(|&other| other == platform)
+        .expect("every platform is listed");
+    out.push(platform as u8);
+
+    out.push(quirks.shift_uses_vy as u8);
+    out.push(match quirks.index_increment {
+        IndexIncrement::Unchanged => 0,
+        IndexIncrement::ByX => 1,
+        IndexIncrement::ByXPlusOne => 2,
+    });
+    out.push(quirks.jump_uses_vx as u8);
+    out.push(quirks.index_overflow_sets_vf as u8);
+    out.push(quirks.clip_sprites as u8);
+    out.push(quirks.logic_resets_vf as u8);
+    out.push(quirks.wait_for_release as u8);
+}
+
+pub(crate) fn read_platform(reader: &mut Reader) -> Result<(Platform, Quirks), StateError> {
+    let platform = match Platform::ALL.get(reader.u8()? as usize) {
+        Some(&platform) => platform,
+        None => return Err(StateError::Invalid("platform")),
+    };
+
+    let quirks = Quirks {
+        shift_uses_vy: reader.bool()?,
+        index_increment: match reader.u8()? {
+            0 => IndexIncrement::Unchanged,
+            1 => IndexIncrement::ByX,
+            2 => IndexIncrement::ByXPlusOne,
+            _ => return Err(StateError::Invalid("index increment quirk")),
+        },
+        jump_uses_vx: reader.bool()?,
+        index_overflow_sets_vf: reader.bool()?,
+        clip_sprites: reader.bool()?,
+        logic_resets_vf: reader.bool()?,
+        wait_for_release: reader.bool()?,
+    };
+
+    Ok((platform, quirks))
+}
+
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    pub(crate) fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + len)
+            .ok_or(StateError::Truncated)?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, StateError> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, StateError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(StateError::Invalid("flag")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Sequence;
+
+    const ROM_HASH: [u8; 20] = [7; 20];
+
+    // Keeps a large state of its own and hands out its bytes in turn.
+    #[derive(Clone)]
+    struct Wide {
+        state: Vec<u8>,
+    }
+
+    impl RandomSource for Wide {
+        fn next_byte(&mut self, _memory: &[u8]) -> u8 {
+            self.state.rotate_left(1);
+            self.state[0]
+        }
+
+        fn save(&self) -> Vec<u8> {
+            self.state.clone()
+        }
+
+        fn restore(&mut self, state: &[u8]) -> bool {
+            if state.len() != self.state.len() {
+                return false;
+            }
+            self.state.copy_from_slice(state);
+            true
+        }
+
+        fn clone_box(&self) -> Box<dyn RandomSource> {
+            Box::new(self.clone())
+        }
+    }
+
+    fn machine(random: impl RandomSource + 'static) -> Chip8 {
+        let mut chip8 = Chip8::with_platform(Platform::Chip8);
+        chip8.random = Box::new(random);
+        chip8
+    }
+
+    #[test]
+    fn sequence_continues_after_load() {
+        let mut saved = machine(Sequence::new(vec![1, 2, 3, 4, 5]));
+        saved.random.next_byte(&[]);
+        saved.random.next_byte(&[]);
+        saved.V[3] = 0x42;
+        saved.pc = 0x2A4;
+        let state = saved.save_state(&ROM_HASH);
+
+        let mut loaded = machine(Sequence::new(vec![1, 2, 3, 4, 5]));
+        loaded.load_state(&state, &ROM_HASH).unwrap();
+
+        assert_eq!(loaded.V[3], 0x42);
+        assert_eq!(loaded.pc, 0x2A4);
+        assert_eq!(loaded.random.next_byte(&[]), 3);
+        assert_eq!(loaded.random.next_byte(&[]), 4);
+    }
+
+    #[test]
+    fn random_state_longer_than_a_byte_count() {
+        let state: Vec<u8> = (0..300).map(|i| i as u8).collect();
+        let mut saved = machine(Wide { state });
+        for _ in 0..280 {
+            saved.random.next_byte(&[]);
+        }
+        let state = saved.save_state(&ROM_HASH);
+
+        let mut loaded = machine(Wide {
+            state: vec![0; 300],
+        });
+        loaded.load_state(&state, &ROM_HASH).unwrap();
+
+        assert_eq!(loaded.random.save(), saved.random.save());
+        assert_eq!(loaded.random.next_byte(&[]), saved.random.next_byte(&[]));
+    }
+
+    #[test]
+    fn other_rom_is_refused() {
+        let state = machine(Sequence::new(vec![1])).save_state(&ROM_HASH);
+        let mut loaded = machine(Sequence::new(vec![1]));
+        assert_eq!(
+            loaded.load_state(&state, &[8; 20]),
+            Err(StateError::WrongRom)
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::Duration;
+
+use crate::clock::Clock;
+use crate::cpu::Chip8;
+
+/// The delay and sound timers count down at this rate.
+pub const TIMER_HZ: u32 = 60;
+
+/// Decrements the delay and sound timers at exactly 60Hz, however often
+/// [`Timers::update`] is called. Fractions of a tick carry over between calls.
+#[derive(Debug, Clone)]
+pub struct Timers {
+    clock: Clock,
+}
+
+impl Default for Timers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Timers {
+    pub fn new() -> Self {
+        Self {
+            clock: Clock::new(TIMER_HZ),
+        }
+    }
+
+    /// Advances by `elapsed` wall-clock time and returns the number of ticks applied.
+    pub fn update(&mut self, chip8: &mut Chip8, elapsed: Duration) -> u32 {
+        let ticks = self.clock.advance(elapsed);
+        chip8.tick_timers_n(ticks);
+        ticks
+    }
+}
+
+impl Chip8 {
+    /// Decrements the delay and sound timers once.
+    pub fn tick_timers(&mut self) {
+        self.tick_timers_n(1);
+    }
+
+    /// Decrements the delay and sound timers as if `n` 60Hz ticks had passed.
+    pub fn tick_timers_n(&mut self, n: u32) {
+        let n = n.min(u8::MAX as u32) as u8;
+        self.delay_timer = self.delay_timer.saturating_sub(n);
+        self.sound_timer = self.sound_timer.saturating_sub(n);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_timers(delay: u8, sound: u8) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.delay_timer = delay;
+        chip8.sound_timer = sound;
+        chip8
+    }
+
+    #[test]
+    fn ticks_decrement_both_timers() {
+        let mut chip8 = with_timers(10, 3);
+        chip8.tick_timers();
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (9, 2));
+        chip8.tick_timers_n(2);
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (7, 0));
+    }
+
+    #[test]
+    fn timers_stop_at_zero() {
+        let mut chip8 = with_timers(5, 0);
+        chip8.tick_timers_n(6);
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (0, 0));
+        chip8.tick_timers_n(1000);
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (0, 0));
+    }
+
+    #[test]
+    fn many_ticks_empty_a_full_timer() {
+        let mut chip8 = with_timers(255, 255);
+        chip8.tick_timers_n(u32::MAX);
+        assert_eq!((chip8.delay_timer, chip8.sound_timer), (0, 0));
+    }
+
+    #[test]
+    fn update_ticks_at_60_hz() {
+        let mut chip8 = with_timers(100, 100);
+        let mut timers = Timers::new();
+        assert_eq!(timers.update(&mut chip8, Duration::from_millis(10)), 0);
+        assert_eq!(timers.update(&mut chip8, Duration::from_millis(10)), 1);
+        assert_eq!(chip8.delay_timer, 99);
+
+        let ticks: u32 = (0..98)
+            .map(|_| timers.update(&mut chip8, Duration::from_millis(10)))
+            .sum();
+        assert_eq!(ticks, 59);
+        assert_eq!(chip8.delay_timer, 40);
+    }
+}
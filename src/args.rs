@@ -0,0 +1,147 @@
+use bevy::prelude::Resource;
+use chip8_core::{BeeperConfig, Breakpoint, Platform, Quirks, RandomKind};
+
+use std::env;
+use std::fmt;
+use std::process;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const USAGE: &str = "Usage: chip-8-emulator <rom> [--hz N] [--platform chip8|schip|xochip] \
+                     [--quirks vip|chip48|schip|xochip|modern|amiga] [--pitch HZ] [--volume V] \
+                     [--mute] [--seed N] [--random xorshift|vip] [--integer-scale] [--fullscreen] \
+                     [--palette NAME|COLORS] [--keys qwerty|azerty|dvorak|numpad] [--no-keypad] \
+                     [--config FILE] [--disasm] [--break SPEC]... [--record FILE | --replay FILE]";
+
+// Command-line options, parsed once before the app starts.
+#[derive(Resource)]
+pub struct Args {
+    pub rom_path: String,
+    // Left unset when not given, so the ROM database can fill them in.
+    pub cpu_hz: Option<u32>,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub beeper: BeeperConfig,
+    pub integer_scale: bool,
+    pub fullscreen: bool,
+    // Show the clickable keypad beside the display.
+    pub keypad: bool,
+    // Built-in or config palette name, or a list of hex colors.
+    pub palette: Option<String>,
+    pub config: Option<String>,
+    // Key layout preset.
+    pub keys: Option<String>,
+    // Where Cxkk gets its numbers from. The seed is picked from the clock
+    // unless --seed gives one.
+    pub random: RandomKind,
+    pub seed: u64,
+    // Print a disassembly of the ROM and exit instead of running it.
+    pub disasm: bool,
+    pub breakpoints: Vec<Breakpoint>,
+    // Movie file to record the input to, or to play it back from.
+    pub record: Option<String>,
+    pub replay: Option<String>,
+}
+
+impl Args {
+    pub fn parse() -> Self {
+        let mut rom_path = None;
+        let mut cpu_hz = None;
+        let mut platform = None;
+        let mut quirks = None;
+        let mut beeper = BeeperConfig::default();
+        let mut integer_scale = false;
+        let mut fullscreen = false;
+        let mut keypad = true;
+        let mut palette = None;
+        let mut config = None;
+        let mut keys = None;
+        let mut random = RandomKind::default();
+        let mut seed = None;
+        let mut disasm = false;
+        let mut breakpoints = Vec::new();
+        let mut record = None;
+        let mut replay = None;
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--hz" => cpu_hz = Some(parse_value::<u32>(args.next()).max(1)),
+                "--platform" => platform = Some(parse_value(args.next())),
+                "--quirks" => quirks = Some(parse_value(args.next())),
+                "--pitch" => beeper.pitch = parse_value(args.next()),
+                "--volume" => beeper.volume = parse_value(args.next()),
+                "--mute" => beeper.muted = true,
+                "--integer-scale" => integer_scale = true,
+                "--fullscreen" => fullscreen = true,
+                "--no-keypad" => keypad = false,
+                "--palette" => palette = Some(parse_value(args.next())),
+                "--config" => config = Some(parse_value(args.next())),
+                "--keys" => keys = Some(parse_value(args.next())),
+                "--seed" => seed = Some(parse_value(args.next())),
+                "--random" => random = parse_value(args.next()),
+                "--disasm" => disasm = true,
+                "--break" => breakpoints.push(parse_value(args.next())),
+                "--record" => record = Some(parse_value(args.next())),
+                "--replay" => replay = Some(parse_value(args.next())),
+                _ if rom_path.is_none() => rom_path = Some(arg),
+                _ => exit_with_usage(),
+            }
+        }
+
+        let Some(rom_path) = rom_path else {
+            eprintln!("Please provide the ROM path !");
+            exit_with_usage();
+        };
+        if record.is_some() && replay.is_some() {
+            exit_with_usage();
+        }
+
+        Self {
+            rom_path,
+            cpu_hz,
+            platform,
+            quirks,
+            beeper,
+            integer_scale,
+            fullscreen,
+            keypad,
+            palette,
+            config,
+            keys,
+            random,
+            seed: seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |time| time.as_nanos() as u64)
+            }),
+            disasm,
+            breakpoints,
+            record,
+            replay,
+        }
+    }
+}
+
+// Exits with the reason and the usage text if `arg` is missing or invalid.
+fn parse_value<T>(arg: Option<String>) -> T
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    let Some(arg) = arg else {
+        exit_with_usage();
+    };
+    match arg.parse() {
+        Ok(value) => value,
+        Err(err) => {
+            eprintln!("{}", err);
+            exit_with_usage();
+        }
+    }
+}
+
+fn exit_with_usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
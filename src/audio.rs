@@ -0,0 +1,125 @@
+use crate::args::Args;
+use crate::debugger::Debugger;
+use crate::rewind::Rewinding;
+use crate::Cpu;
+use bevy::audio::{AddAudioSource, Decodable, Source};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use chip8_core::Speaker;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+pub struct BeeperPlugin;
+
+impl Plugin for BeeperPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Beep>()
+            .add_startup_system(start_beeper)
+            .add_system(toggle_mute)
+            .add_system(update_beeper.after(toggle_mute));
+    }
+}
+
+// Audio asset that streams whatever the shared speaker is playing. The
+// speaker itself decides between silence, the beeper tone and the XO-CHIP
+// audio pattern, so the stream never has to be restarted.
+#[derive(TypeUuid)]
+#[uuid = "5b0a4cd4-3c56-4a0e-9a57-62a83c1d8f7e"]
+pub struct Beep {
+    speaker: Arc<Mutex<Speaker>>,
+}
+
+pub struct BeepDecoder {
+    speaker: Arc<Mutex<Speaker>>,
+    sample_rate: u32,
+}
+
+impl Iterator for BeepDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.speaker.lock().ok()?.next()
+    }
+}
+
+impl Source for BeepDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for Beep {
+    type DecoderItem = f32;
+    type Decoder = BeepDecoder;
+
+    fn decoder(&self) -> BeepDecoder {
+        let sample_rate = self
+            .speaker
+            .lock()
+            .map_or(0, |speaker| speaker.sample_rate());
+        BeepDecoder {
+            speaker: self.speaker.clone(),
+            sample_rate,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct Beeper {
+    speaker: Arc<Mutex<Speaker>>,
+}
+
+fn start_beeper(
+    mut commands: Commands,
+    mut beeps: ResMut<Assets<Beep>>,
+    audio: Res<Audio<Beep>>,
+    args: Res<Args>,
+) {
+    let speaker = Arc::new(Mutex::new(Speaker::new(args.beeper)));
+    let beep = beeps.add(Beep {
+        speaker: speaker.clone(),
+    });
+    audio.play(beep);
+
+    commands.insert_resource(Beeper { speaker });
+}
+
+fn update_beeper(
+    cpu: Res<Cpu>,
+    debugger: Res<Debugger>,
+    rewinding: Res<Rewinding>,
+    beeper: Res<Beeper>,
+) {
+    if let Ok(mut speaker) = beeper.speaker.lock() {
+        if debugger.is_paused() || rewinding.0 {
+            speaker.stop();
+        } else {
+            speaker.update(&cpu);
+        }
+    }
+}
+
+// M is a letter, so it is left to the debugger's prompt while one is open.
+fn toggle_mute(input: Res<Input<KeyCode>>, beeper: Res<Beeper>, debugger: Res<Debugger>) {
+    if debugger.is_prompting() {
+        return;
+    }
+    if input.just_pressed(KeyCode::M) {
+        if let Ok(mut speaker) = beeper.speaker.lock() {
+            speaker.config.muted = !speaker.config.muted;
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use crate::args::Args;
+use bevy::prelude::Resource;
+use serde::Deserialize;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+use std::process;
+
+// Read when --config is not given and the file exists.
+const DEFAULT_PATH: &str = "chip8.toml";
+
+// Settings from the config file. Every table and key is optional; keys this
+// version does not know about are ignored.
+#[derive(Resource, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub palette: Option<String>,
+    pub layout: Option<String>,
+    // The database/ folder of a chip-8-database checkout, read instead of the
+    // bundled copy.
+    pub database: Option<String>,
+    pub palettes: BTreeMap<String, Value>,
+    pub keys: BTreeMap<String, Value>,
+    pub gamepad: GamepadConfig,
+    // [rom.<sha1>] tables, keyed by the ROM's SHA-1 in lowercase hex.
+    pub rom: HashMap<String, RomConfig>,
+}
+
+// Settings that apply to one ROM only.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct RomConfig {
+    pub layout: Option<String>,
+    pub keys: BTreeMap<String, Value>,
+    pub gamepad: GamepadConfig,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+pub struct GamepadConfig {
+    pub deadzone: Option<f32>,
+    pub left_stick: Option<Vec<String>>,
+    pub right_stick: Option<Vec<String>>,
+    // Every other key names a button.
+    #[serde(flatten)]
+    pub buttons: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum Value {
+    Single(String),
+    List(Vec<String>),
+}
+
+impl Value {
+    // A single value counts as a list of one.
+    pub fn as_list(&self) -> Vec<&str> {
+        match self {
+            Value::Single(value) => vec![value.as_str()],
+            Value::List(values) => values.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+impl Config {
+    // Reads --config, or chip8.toml if there is one. Exits on errors, like
+    // bad command-line options do.
+    pub fn load(args: &Args) -> Config {
+        let path = match &args.config {
+            Some(path) => path.as_str(),
+            None if Path::new(DEFAULT_PATH).exists() => DEFAULT_PATH,
+            None => return Config::default(),
+        };
+
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) => {
+                eprintln!("Could not read {}: {}", path, err);
+                process::exit(1);
+            }
+        };
+        match toml::from_str(&text) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("{}: {}", path, err);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_rom_tables_and_gamepad_buttons() {
+        let text = r#"
+            layout = "azerty"
+
+            [keys]
+            5 = ["Z", "Up"]
+
+            [gamepad]
+            south = "5"
+            right_stick = ["none", "none", "4", "6"]
+            deadzone = 0.3
+
+            [rom.0123abcd.keys]
+            4 = "Up"
+        "#;
+        let config: Config = toml::from_str(text).unwrap();
+
+        assert_eq!(config.layout.as_deref(), Some("azerty"));
+        assert_eq!(config.keys["5"].as_list(), ["Z", "Up"]);
+        assert_eq!(config.gamepad.deadzone, Some(0.3));
+        assert_eq!(config.gamepad.right_stick.as_ref().unwrap().len(), 4);
+        assert_eq!(config.gamepad.buttons["south"], "5");
+        assert_eq!(config.gamepad.buttons.len(), 1);
+        assert_eq!(config.rom["0123abcd"].keys["4"].as_list(), ["Up"]);
+    }
+}
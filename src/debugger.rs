@@ -0,0 +1,363 @@
+use std::fmt::Write;
+
+use bevy::prelude::*;
+use chip8_core::{BreakReason, Breakpoint, Breakpoints, Chip8, Instruction, KeyWait};
+
+use super::args::Args;
+use super::ui::UiFont;
+use super::Cpu;
+
+const PANEL_FONT_SIZE: f32 = 14.0;
+
+pub struct DebuggerPlugin;
+
+impl Plugin for DebuggerPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Debugger>()
+            .init_resource::<UiFont>()
+            .add_startup_system(spawn_panel)
+            .add_startup_system(add_breakpoints)
+            .add_system(debugger_hotkeys)
+            .add_system(prompt_input.after(debugger_hotkeys))
+            .add_system(update_panel.after(prompt_input));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RunState {
+    #[default]
+    Running,
+    Paused,
+    // Execute exactly one instruction, then pause again.
+    Step,
+    // Run until pc reaches addr. Step over also waits for the stack pointer
+    // to come back down so recursive calls do not stop early.
+    RunTo {
+        addr: u16,
+        sp: Option<u8>,
+    },
+}
+
+// Step debugger driven by the function keys. The cycle and timer systems ask
+// it before doing anything, so pausing freezes the whole machine.
+#[derive(Resource, Default)]
+pub struct Debugger {
+    pub state: RunState,
+    pub visible: bool,
+    pub breakpoints: Breakpoints,
+    // Why execution last stopped on its own, shown until it resumes.
+    pub reason: Option<BreakReason>,
+    // Text typed after F8 or F9, until Enter or Escape.
+    prompt: Option<Prompt>,
+    // Parse error from the last prompt.
+    message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptKind {
+    RunTo,
+    Break,
+}
+
+struct Prompt {
+    kind: PromptKind,
+    text: String,
+}
+
+impl Debugger {
+    pub fn is_paused(&self) -> bool {
+        self.state == RunState::Paused
+    }
+
+    // While a prompt is open the keyboard belongs to it.
+    pub fn is_prompting(&self) -> bool {
+        self.prompt.is_some()
+    }
+
+    // Instructions that must run this frame even if the CPU clock has none
+    // due, so a single step always shows up immediately.
+    pub fn forced_steps(&self) -> u32 {
+        u32::from(self.state == RunState::Step)
+    }
+
+    // Called before every instruction; returns false if the debugger wants
+    // the CPU to stop in front of it.
+    pub fn allow_step(&mut self, cpu: &Chip8) -> bool {
+        match self.state {
+            RunState::Running => true,
+            RunState::Paused => false,
+            RunState::Step => {
+                self.state = RunState::Paused;
+                true
+            }
+            RunState::RunTo { addr, sp } => {
+                if cpu.pc == addr && sp.is_none_or(|sp| cpu.sp <= sp) {
+                    self.state = RunState::Paused;
+                    false
+                } else {
+                    true
+                }
+            }
+        }
+    }
+
+    // Pauses and opens the panel, e.g. after the program faults.
+    pub fn pause(&mut self) {
+        self.state = RunState::Paused;
+        self.visible = true;
+    }
+
+    // Carries on running, e.g. once rewinding has undone a fault.
+    pub fn resume(&mut self) {
+        self.state = RunState::Running;
+        self.reason = None;
+    }
+
+    // Pauses on a breakpoint and reports why.
+    pub fn hit(&mut self, reason: BreakReason) {
+        eprintln!("Break: {}", reason);
+        self.reason = Some(reason);
+        self.pause();
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) {
+        self.prompt = Some(Prompt {
+            kind,
+            text: String::new(),
+        });
+        self.message = None;
+        self.visible = true;
+    }
+
+    fn step_over(&mut self, cpu: &Chip8) {
+        self.state = match cpu.instruction_at(cpu.pc) {
+            Some(Instruction::Call(_)) => RunState::RunTo {
+                addr: cpu.pc.wrapping_add(2),
+                sp: Some(cpu.sp),
+            },
+            _ => RunState::Step,
+        };
+    }
+}
+
+#[derive(Component)]
+struct DebugPanel;
+
+fn spawn_panel(mut commands: Commands, font: Res<UiFont>) {
+    let style = TextStyle {
+        font: font.clone(),
+        font_size: PANEL_FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands.spawn((
+        TextBundle {
+            text: Text::from_section("", style),
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    top: Val::Px(4.0),
+                    left: Val::Px(4.0),
+                    ..default()
+                },
+                padding: UiRect::all(Val::Px(4.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.75).into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        DebugPanel,
+    ));
+}
+
+fn add_breakpoints(args: Res<Args>, mut debugger: ResMut<Debugger>) {
+    for breakpoint in &args.breakpoints {
+        debugger.breakpoints.add(*breakpoint);
+    }
+}
+
+// F1 panel, F5 pause/resume, F8 add breakpoint (Shift clears them), F9 run
+// to address, F10 step over, F11 step.
+fn debugger_hotkeys(input: Res<Input<KeyCode>>, cpu: Res<Cpu>, mut debugger: ResMut<Debugger>) {
+    if debugger.prompt.is_some() {
+        return;
+    }
+
+    if input.just_pressed(KeyCode::F1) {
+        debugger.visible = !debugger.visible;
+    }
+    if input.just_pressed(KeyCode::F5) {
+        debugger.state = match debugger.state {
+            RunState::Paused => RunState::Running,
+            _ => RunState::Paused,
+        };
+    }
+    if input.just_pressed(KeyCode::F8) {
+        if input.any_pressed([KeyCode::LShift, KeyCode::RShift]) {
+            debugger.breakpoints.clear();
+        } else {
+            debugger.open_prompt(PromptKind::Break);
+        }
+    }
+    if input.just_pressed(KeyCode::F9) {
+        debugger.open_prompt(PromptKind::RunTo);
+    }
+    if input.just_pressed(KeyCode::F10) {
+        debugger.step_over(&cpu);
+    }
+    if input.just_pressed(KeyCode::F11) {
+        debugger.state = RunState::Step;
+    }
+
+    if debugger.state != RunState::Paused {
+        debugger.reason = None;
+    }
+}
+
+fn prompt_input(
+    input: Res<Input<KeyCode>>,
+    mut chars: EventReader<ReceivedCharacter>,
+    mut debugger: ResMut<Debugger>,
+) {
+    let Some(prompt) = debugger.prompt.as_mut() else {
+        chars.clear();
+        return;
+    };
+
+    for event in chars.iter() {
+        let accepted = match prompt.kind {
+            PromptKind::RunTo => event.char.is_ascii_hexdigit() && prompt.text.len() < 4,
+            PromptKind::Break => event.char.is_ascii_graphic() || event.char == ' ',
+        };
+        if accepted {
+            prompt.text.push(event.char);
+        }
+    }
+    if input.just_pressed(KeyCode::Back) {
+        prompt.text.pop();
+    }
+
+    if input.just_pressed(KeyCode::Escape) {
+        debugger.prompt = None;
+    } else if input.just_pressed(KeyCode::Return) {
+        let Some(prompt) = debugger.prompt.take() else {
+            return;
+        };
+        match prompt.kind {
+            PromptKind::RunTo => {
+                if let Ok(addr) = u16::from_str_radix(&prompt.text, 16) {
+                    debugger.state = RunState::RunTo { addr, sp: None };
+                }
+            }
+            PromptKind::Break => match prompt.text.parse::<Breakpoint>() {
+                Ok(breakpoint) => debugger.breakpoints.add(breakpoint),
+                Err(err) => debugger.message = Some(err.to_string()),
+            },
+        }
+    }
+}
+
+fn update_panel(
+    cpu: Res<Cpu>,
+    debugger: Res<Debugger>,
+    mut panel_query: Query<(&mut Text, &mut Visibility), With<DebugPanel>>,
+) {
+    let Ok((mut text, mut visibility)) = panel_query.get_single_mut() else {
+        return;
+    };
+
+    if !debugger.visible {
+        *visibility = Visibility::Hidden;
+        return;
+    }
+    *visibility = Visibility::Visible;
+    text.sections[0].value = panel_text(&cpu, &debugger);
+}
+
+fn panel_text(cpu: &Chip8, debugger: &Debugger) -> String {
+    let mut out = String::new();
+
+    let state = match debugger.state {
+        RunState::Running => "RUNNING".to_string(),
+        RunState::Paused | RunState::Step => "PAUSED".to_string(),
+        RunState::RunTo { addr, .. } => format!("RUN TO {:#05X}", addr),
+    };
+    let _ = writeln!(out, "{}", state);
+    if let Some(reason) = &debugger.reason {
+        let _ = writeln!(out, "Break: {}", reason);
+    }
+
+    let instruction = match cpu.instruction_at(cpu.pc) {
+        Some(instruction) => instruction.to_string(),
+        None => "???".to_string(),
+    };
+    let _ = writeln!(out, "PC {:#06X}  {}", cpu.pc, instruction);
+    let _ = writeln!(out, "I  {:#06X}  SP {}", cpu.I, cpu.sp);
+
+    for (row, registers) in cpu.V.chunks(4).enumerate() {
+        for (col, value) in registers.iter().enumerate() {
+            let _ = write!(out, "V{:X} {:02X}  ", row * 4 + col, value);
+        }
+        out.push('\n');
+    }
+
+    let _ = writeln!(
+        out,
+        "DT {:02X}  ST {:02X}",
+        cpu.delay_timer, cpu.sound_timer
+    );
+
+    out.push_str("Stack");
+    for addr in &cpu.stack[..cpu.sp as usize] {
+        let _ = write!(out, " {:03X}", addr);
+    }
+    out.push('\n');
+
+    out.push_str("Keys  ");
+    for (key, &state) in cpu.keypad.iter().enumerate() {
+        if state == 1 {
+            let _ = write!(out, "{:X}", key);
+        } else {
+            out.push('.');
+        }
+    }
+    match cpu.key_wait {
+        Some(KeyWait { key: None, .. }) => out.push_str("  waiting for a key"),
+        Some(KeyWait { key: Some(key), .. }) => {
+            let _ = write!(out, "  waiting for {:X} to be released", key);
+        }
+        None => (),
+    }
+    out.push('\n');
+
+    if !debugger.breakpoints.is_empty() {
+        let list: Vec<String> = debugger
+            .breakpoints
+            .iter()
+            .map(|breakpoint| breakpoint.to_string())
+            .collect();
+        let _ = writeln!(out, "Breaks {}", list.join(", "));
+    }
+    if let Some(message) = &debugger.message {
+        let _ = writeln!(out, "{}", message);
+    }
+
+    match &debugger.prompt {
+        Some(Prompt {
+            kind: PromptKind::RunTo,
+            text,
+        }) => {
+            let _ = write!(out, "Run to: {}_", text);
+        }
+        Some(Prompt {
+            kind: PromptKind::Break,
+            text,
+        }) => {
+            let _ = write!(out, "Break on: {}_", text);
+        }
+        None => out.push_str("F5 run/pause  F11 step  F10 over  F9 run to  F8 break"),
+    }
+
+    out
+}
@@ -1,54 +1,170 @@
+use crate::args::Args;
+use crate::keypad::KeypadPanel;
+use crate::palette::Palettes;
 use crate::Cpu;
+use crate::WINDOW_SIZE;
 use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+use bevy::window::{PrimaryWindow, WindowMode};
+use chip8_core::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
 
-const TILE_SIZE: f32 = 10.0;
-
-pub fn draw_pixel(mut commands: Commands, mut cpu: ResMut<Cpu>) {
-    if cpu.redraw {
-        for row in 0..32 {
-            for col in 0..64 {
-                // (31 - row) to set (0,0) at top left
-                if cpu.display[(31 - row)][col] == 1 {
-                    commands.spawn((
-                        SpriteBundle {
-                            sprite: Sprite {
-                                // Check if pixel active
-                                color: Color::WHITE,
-                                custom_size: Some(Vec2::new(TILE_SIZE, TILE_SIZE)),
-                                ..default()
-                            },
-                            // texture: asset_server.load("pixel.png"),
-                            transform: Transform::from_xyz(
-                                ((col as f32) * TILE_SIZE) + TILE_SIZE / 2.0,
-                                ((row as f32) * TILE_SIZE) + TILE_SIZE / 2.0,
-                                0.,
-                            ),
-                            ..default()
-                        },
-                        Pixel {
-                            row: (31 - row),
-                            col: col,
-                        },
-                    ));
-                }
-            }
-        }
-        cpu.redraw = false;
+// The display as a single texture with one texel per CHIP-8 pixel. It is
+// rewritten in place on redraw and stretched over the window without
+// smoothing, so drawing costs the same however many pixels are lit.
+#[derive(Resource, Deref)]
+pub struct Screen(pub Handle<Image>);
+
+impl FromWorld for Screen {
+    fn from_world(world: &mut World) -> Self {
+        let mut image = Image::new_fill(
+            texture_size(DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            TextureDimension::D2,
+            &[0, 0, 0, 255],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        image.sampler_descriptor = ImageSampler::nearest();
+        Screen(world.resource_mut::<Assets<Image>>().add(image))
+    }
+}
+
+fn texture_size(width: usize, height: usize) -> Extent3d {
+    Extent3d {
+        width: width as u32,
+        height: height as u32,
+        depth_or_array_layers: 1,
     }
 }
 
-pub fn remove_pixel(mut commands: Commands, pixels: Query<(Entity, &Pixel)>, cpu: Res<Cpu>) {
-    if cpu.redraw {
-        for (entity, pixel_ref) in pixels.iter() {
-            if cpu.display[pixel_ref.row][pixel_ref.col] == 0 {
-                commands.entity(entity).despawn();
-            }
+// How the display is fitted into the window. Integer scaling keeps every
+// CHIP-8 pixel the same number of screen pixels at the cost of wider bars.
+#[derive(Resource)]
+pub struct Scaling {
+    pub integer: bool,
+}
+
+impl FromWorld for Scaling {
+    fn from_world(world: &mut World) -> Self {
+        Scaling {
+            integer: world.resource::<Args>().integer_scale,
         }
     }
 }
 
+// The sprite showing the Screen texture, centered on the camera.
 #[derive(Component)]
-pub struct Pixel {
-    row: usize,
-    col: usize,
+pub struct ScreenSprite;
+
+pub fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub fn spawn_screen(mut commands: Commands, screen: Res<Screen>) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite {
+                custom_size: Some(Vec2::new(WINDOW_SIZE.0, WINDOW_SIZE.1)),
+                ..default()
+            },
+            texture: screen.clone(),
+            ..default()
+        },
+        ScreenSprite,
+    ));
+}
+
+// Sizes the display to the largest that fits the window with the aspect ratio
+// of the active display mode, leaving clear-colored bars on the other sides.
+// Runs every frame, so window resizes and hi-res switches are both followed.
+// The keypad panel, when shown, keeps the right edge of the window.
+pub fn fit_screen(
+    cpu: Res<Cpu>,
+    scaling: Res<Scaling>,
+    keypad: Res<KeypadPanel>,
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    mut sprite_query: Query<(&mut Sprite, &mut Transform), With<ScreenSprite>>,
+) {
+    let (Ok(window), Ok((mut sprite, mut transform))) =
+        (window_query.get_single(), sprite_query.get_single_mut())
+    else {
+        return;
+    };
+
+    let offset = -keypad.width() / 2.0;
+    if transform.translation.x != offset {
+        transform.translation.x = offset;
+    }
+
+    // Worked out in physical pixels so integer scaling stays exact on
+    // high-DPI screens.
+    let (width, height) = cpu.display_size();
+    let panel_width = keypad.width() * window.scale_factor() as f32;
+    let mut scale = f32::min(
+        (window.physical_width() as f32 - panel_width).max(0.0) / width as f32,
+        window.physical_height() as f32 / height as f32,
+    );
+    if scaling.integer {
+        scale = scale.floor().max(1.0);
+    }
+
+    let size = Vec2::new(width as f32, height as f32) * scale / window.scale_factor() as f32;
+    if sprite.custom_size != Some(size) {
+        sprite.custom_size = Some(size);
+    }
+}
+
+// F12 toggles integer scaling, Alt+Enter fullscreen. Shift+F12 is the
+// keypad's.
+pub fn scaling_hotkeys(
+    input: Res<Input<KeyCode>>,
+    mut scaling: ResMut<Scaling>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    let shift = input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if !shift && input.just_pressed(KeyCode::F12) {
+        scaling.integer = !scaling.integer;
+    }
+
+    let alt = input.any_pressed([KeyCode::LAlt, KeyCode::RAlt]);
+    if alt && input.just_pressed(KeyCode::Return) {
+        if let Ok(mut window) = window_query.get_single_mut() {
+            window.mode = match window.mode {
+                WindowMode::Windowed => WindowMode::BorderlessFullscreen,
+                _ => WindowMode::Windowed,
+            };
+        }
+    }
+}
+
+// Unlit pixels take the palette's first color and are drawn opaque, so the
+// display stands apart from the letterbox bars around it.
+pub fn draw_screen(
+    mut cpu: ResMut<Cpu>,
+    screen: Res<Screen>,
+    palettes: Res<Palettes>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    if !cpu.redraw && !palettes.is_changed() {
+        return;
+    }
+    let Some(image) = images.get_mut(&screen) else {
+        return;
+    };
+
+    // Hi-res mode packs more texels into the same sprite.
+    let (width, height) = cpu.display_size();
+    let size = texture_size(width, height);
+    if image.texture_descriptor.size != size {
+        image.resize(size);
+    }
+
+    let colors = palettes
+        .current()
+        .colors
+        .map(|color| color.as_rgba_u32().to_le_bytes());
+    for (i, texel) in image.data.chunks_exact_mut(4).enumerate() {
+        let planes = cpu.pixel_planes(i % width, i / width) & 0b11;
+        texel.copy_from_slice(&colors[planes as usize]);
+    }
+    cpu.redraw = false;
 }
@@ -1,6 +1,16 @@
+use super::args::Args;
+use super::config::{Config, GamepadConfig, RomConfig, Value};
+use super::debugger::Debugger;
+use super::loader::RomHash;
+use super::movie::MovieState;
+use super::romdb::RomInfo;
 use super::Cpu;
+use bevy::input::gamepad::{GamepadConnection, GamepadConnectionEvent};
 use bevy::prelude::*;
 
+use std::collections::BTreeMap;
+use std::process;
+
 /*            Remap
 Keypad                   Keyboard
 +-+-+-+-+                +-+-+-+-+
@@ -12,102 +22,687 @@ Keypad                   Keyboard
 +-+-+-+-+                +-+-+-+-+
 |A|0|B|F|                |Z|X|C|V|
 +-+-+-+-+                +-+-+-+-+
+
+The other presets put the same grid on the same physical keys of their
+layout; "numpad" uses 789/ 456* 123- 0.Enter+ instead.
 */
 
-pub fn handle_input(input: Res<Input<KeyCode>>, mut cpu: ResMut<Cpu>) {
-    if input.pressed(KeyCode::Key1) {
-        cpu.keypad[1] = 1;
-    } else {
-        cpu.keypad[1] = 0
+// Keys for CHIP-8 keys 0 to F.
+const PRESETS: [(&str, [KeyCode; 16]); 4] = [
+    (
+        "qwerty",
+        [
+            KeyCode::X,
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Q,
+            KeyCode::W,
+            KeyCode::E,
+            KeyCode::A,
+            KeyCode::S,
+            KeyCode::D,
+            KeyCode::Z,
+            KeyCode::C,
+            KeyCode::Key4,
+            KeyCode::R,
+            KeyCode::F,
+            KeyCode::V,
+        ],
+    ),
+    (
+        "azerty",
+        [
+            KeyCode::X,
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::A,
+            KeyCode::Z,
+            KeyCode::E,
+            KeyCode::Q,
+            KeyCode::S,
+            KeyCode::D,
+            KeyCode::W,
+            KeyCode::C,
+            KeyCode::Key4,
+            KeyCode::R,
+            KeyCode::F,
+            KeyCode::V,
+        ],
+    ),
+    (
+        "dvorak",
+        [
+            KeyCode::Q,
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Apostrophe,
+            KeyCode::Comma,
+            KeyCode::Period,
+            KeyCode::A,
+            KeyCode::O,
+            KeyCode::E,
+            KeyCode::Semicolon,
+            KeyCode::J,
+            KeyCode::Key4,
+            KeyCode::P,
+            KeyCode::U,
+            KeyCode::K,
+        ],
+    ),
+    (
+        "numpad",
+        [
+            KeyCode::NumpadDecimal,
+            KeyCode::Numpad7,
+            KeyCode::Numpad8,
+            KeyCode::Numpad9,
+            KeyCode::Numpad4,
+            KeyCode::Numpad5,
+            KeyCode::Numpad6,
+            KeyCode::Numpad1,
+            KeyCode::Numpad2,
+            KeyCode::Numpad3,
+            KeyCode::Numpad0,
+            KeyCode::NumpadEnter,
+            KeyCode::NumpadDivide,
+            KeyCode::NumpadMultiply,
+            KeyCode::NumpadSubtract,
+            KeyCode::NumpadAdd,
+        ],
+    ),
+];
+
+// Names accepted for keys in the config file, besides letters and digits.
+const KEY_NAMES: [(&str, KeyCode); 39] = [
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("space", KeyCode::Space),
+    ("enter", KeyCode::Return),
+    ("return", KeyCode::Return),
+    ("tab", KeyCode::Tab),
+    ("lshift", KeyCode::LShift),
+    ("rshift", KeyCode::RShift),
+    ("lcontrol", KeyCode::LControl),
+    ("rcontrol", KeyCode::RControl),
+    ("lalt", KeyCode::LAlt),
+    ("ralt", KeyCode::RAlt),
+    ("comma", KeyCode::Comma),
+    ("period", KeyCode::Period),
+    ("semicolon", KeyCode::Semicolon),
+    ("apostrophe", KeyCode::Apostrophe),
+    ("slash", KeyCode::Slash),
+    ("backslash", KeyCode::Backslash),
+    ("minus", KeyCode::Minus),
+    ("equals", KeyCode::Equals),
+    ("lbracket", KeyCode::LBracket),
+    ("rbracket", KeyCode::RBracket),
+    ("grave", KeyCode::Grave),
+    ("numpad0", KeyCode::Numpad0),
+    ("numpad1", KeyCode::Numpad1),
+    ("numpad2", KeyCode::Numpad2),
+    ("numpad3", KeyCode::Numpad3),
+    ("numpad4", KeyCode::Numpad4),
+    ("numpad5", KeyCode::Numpad5),
+    ("numpad6", KeyCode::Numpad6),
+    ("numpad7", KeyCode::Numpad7),
+    ("numpad8", KeyCode::Numpad8),
+    ("numpad9", KeyCode::Numpad9),
+    ("numpadadd", KeyCode::NumpadAdd),
+    ("numpadsubtract", KeyCode::NumpadSubtract),
+    ("numpadmultiply", KeyCode::NumpadMultiply),
+    ("numpaddivide", KeyCode::NumpadDivide),
+];
+
+const LETTERS: [KeyCode; 26] = [
+    KeyCode::A,
+    KeyCode::B,
+    KeyCode::C,
+    KeyCode::D,
+    KeyCode::E,
+    KeyCode::F,
+    KeyCode::G,
+    KeyCode::H,
+    KeyCode::I,
+    KeyCode::J,
+    KeyCode::K,
+    KeyCode::L,
+    KeyCode::M,
+    KeyCode::N,
+    KeyCode::O,
+    KeyCode::P,
+    KeyCode::Q,
+    KeyCode::R,
+    KeyCode::S,
+    KeyCode::T,
+    KeyCode::U,
+    KeyCode::V,
+    KeyCode::W,
+    KeyCode::X,
+    KeyCode::Y,
+    KeyCode::Z,
+];
+
+const DIGITS: [KeyCode; 10] = [
+    KeyCode::Key0,
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+// Parses "W", "7" or "Key7", "Up", "Numpad8" and the other KEY_NAMES.
+fn parse_key(name: &str) -> Option<KeyCode> {
+    let lower = name.to_ascii_lowercase();
+    let digit = lower.strip_prefix("key").unwrap_or(&lower);
+    match digit.as_bytes() {
+        [c @ b'a'..=b'z'] => return Some(LETTERS[(c - b'a') as usize]),
+        [c @ b'0'..=b'9'] => return Some(DIGITS[(c - b'0') as usize]),
+        _ => (),
+    }
+    KEY_NAMES
+        .iter()
+        .find(|(key_name, _)| *key_name == lower)
+        .map(|&(_, code)| code)
+}
+
+// Gamepad buttons and the CHIP-8 keys they press. The D-pad sits where W A
+// S D do on the keyboard, the face buttons on the keys around them.
+const GAMEPAD_BUTTONS: [(GamepadButtonType, usize); 10] = [
+    (GamepadButtonType::DPadUp, 0x5),
+    (GamepadButtonType::DPadDown, 0x8),
+    (GamepadButtonType::DPadLeft, 0x7),
+    (GamepadButtonType::DPadRight, 0x9),
+    (GamepadButtonType::South, 0x6),
+    (GamepadButtonType::West, 0x4),
+    (GamepadButtonType::East, 0xD),
+    (GamepadButtonType::North, 0xC),
+    (GamepadButtonType::Start, 0xF),
+    (GamepadButtonType::Select, 0x0),
+];
+
+// Names for gamepad buttons in the [gamepad] section.
+const BUTTON_NAMES: [(&str, GamepadButtonType); 16] = [
+    ("south", GamepadButtonType::South),
+    ("east", GamepadButtonType::East),
+    ("north", GamepadButtonType::North),
+    ("west", GamepadButtonType::West),
+    ("dpad_up", GamepadButtonType::DPadUp),
+    ("dpad_down", GamepadButtonType::DPadDown),
+    ("dpad_left", GamepadButtonType::DPadLeft),
+    ("dpad_right", GamepadButtonType::DPadRight),
+    ("left_trigger", GamepadButtonType::LeftTrigger),
+    ("left_trigger2", GamepadButtonType::LeftTrigger2),
+    ("right_trigger", GamepadButtonType::RightTrigger),
+    ("right_trigger2", GamepadButtonType::RightTrigger2),
+    ("left_thumb", GamepadButtonType::LeftThumb),
+    ("right_thumb", GamepadButtonType::RightThumb),
+    ("select", GamepadButtonType::Select),
+    ("start", GamepadButtonType::Start),
+];
+
+// The axes of the left and right sticks, and their names in [gamepad].
+const STICKS: [(&str, GamepadAxisType, GamepadAxisType); 2] = [
+    (
+        "left_stick",
+        GamepadAxisType::LeftStickX,
+        GamepadAxisType::LeftStickY,
+    ),
+    (
+        "right_stick",
+        GamepadAxisType::RightStickX,
+        GamepadAxisType::RightStickY,
+    ),
+];
+
+// Where the actions the ROM database names for a ROM go on the keyboard and
+// gamepads: arrow keys, D-pad and left stick for directions, Space and the
+// South button for "a", left Ctrl and the East button for "b".
+const ACTIONS: [(&str, KeyCode, GamepadButtonType, Option<usize>); 6] = [
+    ("up", KeyCode::Up, GamepadButtonType::DPadUp, Some(0)),
+    ("down", KeyCode::Down, GamepadButtonType::DPadDown, Some(1)),
+    ("left", KeyCode::Left, GamepadButtonType::DPadLeft, Some(2)),
+    (
+        "right",
+        KeyCode::Right,
+        GamepadButtonType::DPadRight,
+        Some(3),
+    ),
+    ("a", KeyCode::Space, GamepadButtonType::South, None),
+    ("b", KeyCode::LControl, GamepadButtonType::East, None),
+];
+
+// Keys the emulator's hotkeys use, which the config file cannot bind: the
+// function keys, Backspace to rewind, M to mute, Enter for Alt+Enter and the
+// debugger prompt, which Escape cancels.
+const RESERVED_KEYS: [KeyCode; 16] = [
+    KeyCode::F1,
+    KeyCode::F2,
+    KeyCode::F3,
+    KeyCode::F4,
+    KeyCode::F5,
+    KeyCode::F6,
+    KeyCode::F7,
+    KeyCode::F8,
+    KeyCode::F9,
+    KeyCode::F10,
+    KeyCode::F11,
+    KeyCode::F12,
+    KeyCode::Back,
+    KeyCode::M,
+    KeyCode::Return,
+    KeyCode::Escape,
+];
+
+// How far a stick has to be pushed before it presses a key.
+const DEFAULT_DEADZONE: f32 = 0.5;
+
+// The physical keys held down for each CHIP-8 key, 0 to F. Any of them
+// presses it, as does any bound button or stick direction on any gamepad.
+#[derive(Resource, Clone)]
+pub struct KeyMap {
+    keys: [Vec<KeyCode>; 16],
+    buttons: Vec<(GamepadButtonType, usize)>,
+    // CHIP-8 keys for up, down, left and right on each of STICKS.
+    sticks: [[Option<usize>; 4]; 2],
+    deadzone: f32,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        Self::preset("qwerty").expect("qwerty is a preset")
+    }
+}
+
+impl KeyMap {
+    pub fn preset(name: &str) -> Option<KeyMap> {
+        let (_, codes) = PRESETS
+            .iter()
+            .find(|(preset, _)| preset.eq_ignore_ascii_case(name))?;
+        Some(KeyMap {
+            keys: codes.map(|code| vec![code]),
+            buttons: GAMEPAD_BUTTONS.to_vec(),
+            sticks: [[Some(0x5), Some(0x8), Some(0x7), Some(0x9)], [None; 4]],
+            deadzone: DEFAULT_DEADZONE,
+        })
+    }
+
+    // Replaces the keys of CHIP-8 key `key`. A physical key only ever
+    // presses one CHIP-8 key, so it is taken away from any other.
+    pub fn bind(&mut self, key: usize, codes: Vec<KeyCode>) {
+        for other in self.keys.iter_mut() {
+            other.retain(|code| !codes.contains(code));
+        }
+        self.keys[key] = codes;
+    }
+
+    pub fn keys(&self, key: usize) -> &[KeyCode] {
+        &self.keys[key]
     }
 
-    if input.pressed(KeyCode::Key2) {
-        cpu.keypad[2] = 1
-    } else {
-        cpu.keypad[2] = 0
+    // Applies the bindings of a [keys] section: a hex digit for the CHIP-8
+    // key, then one physical key or a list of them. Hotkeys, a key given for
+    // two CHIP-8 keys in the section and the keys the ROM database's actions
+    // already use for another CHIP-8 key are refused.
+    fn apply(&mut self, section: &str, keys: &BTreeMap<String, Value>) -> Result<(), String> {
+        let mut taken: Vec<(KeyCode, usize)> = Vec::new();
+        for (key, value) in keys {
+            let index = match u8::from_str_radix(key, 16) {
+                Ok(index) if index < 16 => index as usize,
+                _ => return Err(format!("[{}]: '{}' is not a key from 0 to F", section, key)),
+            };
+            let mut codes = Vec::new();
+            for name in value.as_list() {
+                let error = |what: String| format!("[{}]: '{}' {}", section, name, what);
+                let code = parse_key(name).ok_or_else(|| error("is not a key name".into()))?;
+                if RESERVED_KEYS.contains(&code) {
+                    return Err(error("is used by a hotkey".into()));
+                }
+                if let Some(&(_, other)) = taken
+                    .iter()
+                    .find(|&&(c, other)| c == code && other != index)
+                {
+                    return Err(error(format!(
+                        "is bound to both {:X} and {:X}",
+                        other, index
+                    )));
+                }
+                let action = ACTIONS
+                    .iter()
+                    .any(|&(_, action_code, ..)| action_code == code);
+                if let Some(other) = self.key_of(code).filter(|&other| action && other != index) {
+                    return Err(error(format!(
+                        "already presses {:X} for the ROM's controls",
+                        other
+                    )));
+                }
+                taken.push((code, index));
+                codes.push(code);
+            }
+            self.bind(index, codes);
+        }
+        Ok(())
     }
 
-    if input.pressed(KeyCode::Key3) {
-        cpu.keypad[3] = 1
-    } else {
-        cpu.keypad[3] = 0
+    // The CHIP-8 key `code` presses, if any.
+    fn key_of(&self, code: KeyCode) -> Option<usize> {
+        self.keys.iter().position(|codes| codes.contains(&code))
     }
 
-    if input.pressed(KeyCode::Key4) {
-        cpu.keypad[0x0C] = 1
-    } else {
-        cpu.keypad[0x0C] = 0
+    fn bind_button(&mut self, button: GamepadButtonType, key: Option<usize>) {
+        self.buttons.retain(|&(other, _)| other != button);
+        if let Some(key) = key {
+            self.buttons.push((button, key));
+        }
     }
 
-    if input.pressed(KeyCode::Q) {
-        cpu.keypad[4] = 1
-    } else {
-        cpu.keypad[4] = 0
+    // Adds the keys for the actions the ROM database lists, like "left", on
+    // top of the layout.
+    fn apply_actions(&mut self, actions: &[(String, usize)]) {
+        for (action, key) in actions {
+            let Some(&(_, code, button, direction)) =
+                ACTIONS.iter().find(|(name, ..)| name == action)
+            else {
+                continue;
+            };
+            let mut codes = self.keys[*key].clone();
+            codes.push(code);
+            self.bind(*key, codes);
+            self.bind_button(button, Some(*key));
+            if let Some(direction) = direction {
+                self.sticks[0][direction] = Some(*key);
+            }
+        }
     }
 
-    if input.pressed(KeyCode::W) {
-        cpu.keypad[5] = 1
-    } else {
-        cpu.keypad[5] = 0
+    // Applies a [gamepad] section: the deadzone from 0 to 1, four hex digits
+    // (or "none") for up, down, left and right on each stick, and a hex digit
+    // or "none" for each button named.
+    fn apply_gamepad(&mut self, section: &str, gamepad: &GamepadConfig) -> Result<(), String> {
+        let error = |what: &str, name: &str| format!("[{}]: {} for '{}'", section, what, name);
+        if let Some(deadzone) = gamepad.deadzone {
+            if !(0.0..1.0).contains(&deadzone) {
+                return Err(error("expected a number from 0 up to 1", "deadzone"));
+            }
+            self.deadzone = deadzone;
+        }
+        let sticks = [&gamepad.left_stick, &gamepad.right_stick];
+        for (stick, ((name, ..), keys)) in STICKS.iter().zip(sticks).enumerate() {
+            let Some(keys) = keys else {
+                continue;
+            };
+            let keys = keys
+                .iter()
+                .map(|key| parse_keypad_key(key))
+                .collect::<Option<Vec<_>>>();
+            self.sticks[stick] = match keys.as_deref() {
+                Some(&[up, down, left, right]) => [up, down, left, right],
+                _ => return Err(error("expected four keys from 0 to F or \"none\"", name)),
+            };
+        }
+        for (name, value) in &gamepad.buttons {
+            let Some(&(_, button)) = BUTTON_NAMES.iter().find(|(n, _)| n == name) else {
+                return Err(format!("[{}]: unknown gamepad button '{}'", section, name));
+            };
+            let key = parse_keypad_key(value)
+                .ok_or_else(|| error("expected a key from 0 to F or \"none\"", name))?;
+            self.bind_button(button, key);
+        }
+        Ok(())
     }
 
-    if input.pressed(KeyCode::E) {
-        cpu.keypad[6] = 1
-    } else {
-        cpu.keypad[6] = 0
+    // Marks the CHIP-8 keys `gamepad` holds down. Reads only Bevy's input
+    // resources, so gamepad events sent by hand drive it the same as real
+    // ones.
+    fn press_gamepad(
+        &self,
+        gamepad: Gamepad,
+        buttons: &Input<GamepadButton>,
+        axes: &Axis<GamepadAxis>,
+        pressed: &mut [bool; 16],
+    ) {
+        for &(button, key) in &self.buttons {
+            if buttons.pressed(GamepadButton::new(gamepad, button)) {
+                pressed[key] = true;
+            }
+        }
+
+        for ((_, x_axis, y_axis), keys) in STICKS.iter().zip(&self.sticks) {
+            let x = axes.get(GamepadAxis::new(gamepad, *x_axis)).unwrap_or(0.0);
+            let y = axes.get(GamepadAxis::new(gamepad, *y_axis)).unwrap_or(0.0);
+            let directions = [
+                y > self.deadzone,
+                y < -self.deadzone,
+                x < -self.deadzone,
+                x > self.deadzone,
+            ];
+            for (&key, held) in keys.iter().zip(directions) {
+                if let (Some(key), true) = (key, held) {
+                    pressed[key] = true;
+                }
+            }
+        }
+    }
+}
+
+// A hex digit for a CHIP-8 key, or "none" for no key at all.
+fn parse_keypad_key(text: &str) -> Option<Option<usize>> {
+    if text.eq_ignore_ascii_case("none") {
+        return Some(None);
+    }
+    match u8::from_str_radix(text, 16) {
+        Ok(key) if key < 16 => Some(Some(key as usize)),
+        _ => None,
+    }
+}
+
+// Builds the key map once the ROM is known. The layout comes from --keys,
+// then the ROM's own [rom.<sha1>] section, then the top level of the config
+// file. The ROM database's keys for the game's actions are added to it, then
+// [keys] and [rom.<sha1>.keys] move single keys around on top,
+// [gamepad] and [rom.<sha1>.gamepad] rebind gamepad buttons the same way.
+pub fn load_keymap(
+    mut commands: Commands,
+    config: Res<Config>,
+    args: Res<Args>,
+    rom_hash: Res<RomHash>,
+    rom_info: Res<RomInfo>,
+) {
+    let rom_section = format!("rom.{}", rom_hash.hex());
+    let no_rom_config = RomConfig::default();
+    let rom_config = config.rom.get(&rom_hash.hex()).unwrap_or(&no_rom_config);
+    let layout = args
+        .keys
+        .clone()
+        .or_else(|| rom_config.layout.clone())
+        .or_else(|| config.layout.clone());
+
+    let mut keymap = KeyMap::default();
+    if let Some(layout) = layout {
+        keymap = KeyMap::preset(&layout).unwrap_or_else(|| {
+            let names: Vec<&str> = PRESETS.iter().map(|(name, _)| *name).collect();
+            eprintln!(
+                "unknown key layout '{}', expected one of: {}",
+                layout,
+                names.join(", ")
+            );
+            process::exit(1);
+        });
+    }
+
+    keymap.apply_actions(&rom_info.keys);
+
+    let sections = [
+        (String::new(), &config.keys, &config.gamepad),
+        (
+            format!("{}.", rom_section),
+            &rom_config.keys,
+            &rom_config.gamepad,
+        ),
+    ];
+    for (prefix, keys, gamepad) in sections {
+        let result = keymap
+            .apply(&format!("{}keys", prefix), keys)
+            .and_then(|_| keymap.apply_gamepad(&format!("{}gamepad", prefix), gamepad));
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
     }
+    commands.insert_resource(keymap);
+}
 
-    if input.pressed(KeyCode::R) {
-        cpu.keypad[0x0D] = 1
-    } else {
-        cpu.keypad[0x0D] = 0
+// Gamepads are read from Bevy's Gamepads list every frame, so one plugged in
+// while running works straight away and one pulled out releases its keys.
+#[allow(clippy::too_many_arguments)]
+pub fn handle_input(
+    input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    keymap: Res<KeyMap>,
+    mut cpu: ResMut<Cpu>,
+    debugger: Res<Debugger>,
+    movie: Res<MovieState>,
+) {
+    // A replay presses the keys itself.
+    if movie.is_replaying() {
+        return;
+    }
+    // Typing into the debugger's prompt must not play the game.
+    if debugger.is_prompting() {
+        cpu.keypad = [0; 16];
+        return;
     }
 
-    if input.pressed(KeyCode::A) {
-        cpu.keypad[7] = 1
-    } else {
-        cpu.keypad[7] = 0
+    let mut pressed = [false; 16];
+    for (key, state) in pressed.iter_mut().enumerate() {
+        *state = input.any_pressed(keymap.keys(key).iter().copied());
+    }
+    for gamepad in gamepads.iter() {
+        keymap.press_gamepad(gamepad, &buttons, &axes, &mut pressed);
     }
+    for (state, pressed) in cpu.keypad.iter_mut().zip(pressed) {
+        *state = pressed as u8;
+    }
+}
 
-    if input.pressed(KeyCode::S) {
-        cpu.keypad[8] = 1
-    } else {
-        cpu.keypad[8] = 0
+pub fn log_gamepads(mut events: EventReader<GamepadConnectionEvent>) {
+    for event in events.iter() {
+        match &event.connection {
+            GamepadConnection::Connected(info) => {
+                println!("Gamepad {} connected: {}", event.gamepad.id, info.name)
+            }
+            GamepadConnection::Disconnected => {
+                println!("Gamepad {} disconnected", event.gamepad.id)
+            }
+        }
     }
+}
 
-    if input.pressed(KeyCode::D) {
-        cpu.keypad[9] = 1
-    } else {
-        cpu.keypad[9] = 0
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::input::gamepad::GamepadInfo;
+
+    fn section(bindings: &[(&str, &[&str])]) -> BTreeMap<String, Value> {
+        bindings
+            .iter()
+            .map(|(key, names)| {
+                let names = names.iter().map(|name| name.to_string()).collect();
+                (key.to_string(), Value::List(names))
+            })
+            .collect()
     }
 
-    if input.pressed(KeyCode::F) {
-        cpu.keypad[0x0E] = 1
-    } else {
-        cpu.keypad[0x0E] = 0
+    #[test]
+    fn binding_moves_a_key() {
+        let mut keymap = KeyMap::default();
+        keymap
+            .apply("keys", &section(&[("5", &["Z", "Up"])]))
+            .unwrap();
+        assert_eq!(keymap.keys(0x5), [KeyCode::Z, KeyCode::Up]);
+        assert!(keymap.keys(0xA).is_empty());
     }
 
-    if input.pressed(KeyCode::Z) {
-        cpu.keypad[0x0A] = 1
-    } else {
-        cpu.keypad[0x0A] = 0
+    #[test]
+    fn hotkeys_cannot_be_bound() {
+        let mut keymap = KeyMap::default();
+        let err = keymap.apply("keys", &section(&[("5", &["Enter"])]));
+        assert_eq!(err, Err("[keys]: 'Enter' is used by a hotkey".to_string()));
     }
 
-    if input.pressed(KeyCode::X) {
-        cpu.keypad[0] = 1
-    } else {
-        cpu.keypad[0] = 0
+    #[test]
+    fn one_key_for_two_keys_is_refused() {
+        let mut keymap = KeyMap::default();
+        let err = keymap.apply("keys", &section(&[("4", &["Up"]), ("6", &["Up"])]));
+        assert_eq!(
+            err,
+            Err("[keys]: 'Up' is bound to both 4 and 6".to_string())
+        );
     }
 
-    if input.pressed(KeyCode::C) {
-        cpu.keypad[0x0B] = 1
-    } else {
-        cpu.keypad[0x0B] = 0
+    #[test]
+    fn action_keys_are_refused_for_other_keys() {
+        let mut keymap = KeyMap::default();
+        keymap.apply_actions(&[("up".to_string(), 0x5), ("b".to_string(), 0x6)]);
+        assert_eq!(keymap.keys(0x6), [KeyCode::E, KeyCode::LControl]);
+
+        let err = keymap.apply("keys", &section(&[("4", &["Up"])]));
+        assert_eq!(
+            err,
+            Err("[keys]: 'Up' already presses 5 for the ROM's controls".to_string())
+        );
+        keymap.apply("keys", &section(&[("5", &["Up"])])).unwrap();
     }
 
-    if input.pressed(KeyCode::V) {
-        cpu.keypad[0x0F] = 1
-    } else {
-        cpu.keypad[0x0F] = 0
+    #[test]
+    fn gamepad_presses_bound_keys() {
+        let mut keymap = KeyMap::default();
+        let gamepad_config = GamepadConfig {
+            buttons: [("right_trigger".to_string(), "6".to_string())].into(),
+            ..default()
+        };
+        keymap.apply_gamepad("gamepad", &gamepad_config).unwrap();
+
+        let mut app = App::new();
+        app.add_plugin(bevy::input::InputPlugin)
+            .insert_resource(keymap)
+            .init_resource::<Cpu>()
+            .init_resource::<Debugger>()
+            .init_resource::<MovieState>()
+            .add_system(handle_input);
+
+        let gamepad = Gamepad::new(0);
+        app.world.send_event(GamepadConnectionEvent::new(
+            gamepad,
+            GamepadConnection::Connected(GamepadInfo {
+                name: "Test pad".to_string(),
+            }),
+        ));
+        app.update();
+        assert_eq!(app.world.resource::<Cpu>().keypad, [0; 16]);
+
+        app.world
+            .resource_mut::<Input<GamepadButton>>()
+            .press(GamepadButton::new(gamepad, GamepadButtonType::RightTrigger));
+        app.world
+            .resource_mut::<Axis<GamepadAxis>>()
+            .set(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX), -0.9);
+        app.update();
+
+        let mut expected = [0; 16];
+        expected[0x6] = 1;
+        expected[0x7] = 1;
+        assert_eq!(app.world.resource::<Cpu>().keypad, expected);
     }
 }
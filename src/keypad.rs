@@ -0,0 +1,251 @@
+use super::args::Args;
+use super::keymap::KeyMap;
+use super::movie::MovieState;
+use super::palette::Palettes;
+use super::ui::UiFont;
+use super::Cpu;
+use bevy::prelude::*;
+
+// Logical width of the panel at the right edge of the window.
+pub const KEYPAD_WIDTH: f32 = 200.0;
+
+// CHIP-8 keys in the order they sit on the COSMAC VIP keypad.
+const LAYOUT: [[usize; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+const KEY_COLOR: Color = Color::rgb(0.2, 0.2, 0.2);
+const HOVER_COLOR: Color = Color::rgb(0.3, 0.3, 0.3);
+
+// The clickable keypad beside the display. It shows which keyboard key plays
+// each CHIP-8 key and lights up keys however they were pressed.
+#[derive(Resource)]
+pub struct KeypadPanel {
+    pub visible: bool,
+}
+
+impl KeypadPanel {
+    // Window width the panel takes away from the display.
+    pub fn width(&self) -> f32 {
+        if self.visible {
+            KEYPAD_WIDTH
+        } else {
+            0.0
+        }
+    }
+}
+
+impl FromWorld for KeypadPanel {
+    fn from_world(world: &mut World) -> Self {
+        KeypadPanel {
+            visible: world.resource::<Args>().keypad,
+        }
+    }
+}
+
+pub struct KeypadPlugin;
+
+impl Plugin for KeypadPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KeypadPanel>()
+            .init_resource::<UiFont>()
+            .add_startup_system(spawn_keypad)
+            .add_system(keypad_hotkeys)
+            .add_system(label_keys)
+            .add_system(light_keys);
+    }
+}
+
+#[derive(Component)]
+struct KeypadRoot;
+
+#[derive(Component)]
+pub struct KeypadButton(usize);
+
+#[derive(Component)]
+struct KeyDigit(usize);
+
+// The smaller text under the hex digit naming the keyboard key.
+#[derive(Component)]
+struct KeyLabel(usize);
+
+fn spawn_keypad(mut commands: Commands, font: Res<UiFont>, panel: Res<KeypadPanel>) {
+    let digit_style = TextStyle {
+        font: font.clone(),
+        font_size: 24.0,
+        color: Color::WHITE,
+    };
+    let label_style = TextStyle {
+        font: font.clone(),
+        font_size: 11.0,
+        color: Color::GRAY,
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    display: if panel.visible {
+                        Display::Flex
+                    } else {
+                        Display::None
+                    },
+                    position_type: PositionType::Absolute,
+                    position: UiRect {
+                        top: Val::Px(0.0),
+                        bottom: Val::Px(0.0),
+                        right: Val::Px(0.0),
+                        ..default()
+                    },
+                    size: Size::width(Val::Px(KEYPAD_WIDTH)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                ..default()
+            },
+            KeypadRoot,
+        ))
+        .with_children(|root| {
+            for row in LAYOUT {
+                root.spawn(NodeBundle {
+                    style: Style {
+                        size: Size::height(Val::Px(KEYPAD_WIDTH / 4.0 - 4.0)),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|row_node| {
+                    for key in row {
+                        row_node
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        flex_grow: 1.0,
+                                        flex_basis: Val::Px(0.0),
+                                        margin: UiRect::all(Val::Px(3.0)),
+                                        flex_direction: FlexDirection::Column,
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    background_color: KEY_COLOR.into(),
+                                    ..default()
+                                },
+                                KeypadButton(key),
+                            ))
+                            .with_children(|button| {
+                                button.spawn((
+                                    TextBundle::from_section(
+                                        format!("{:X}", key),
+                                        digit_style.clone(),
+                                    ),
+                                    KeyDigit(key),
+                                ));
+                                button.spawn((
+                                    TextBundle::from_section("", label_style.clone()),
+                                    KeyLabel(key),
+                                ));
+                            });
+                    }
+                });
+            }
+        });
+}
+
+// Shift+F12 shows or hides the keypad.
+fn keypad_hotkeys(
+    input: Res<Input<KeyCode>>,
+    mut panel: ResMut<KeypadPanel>,
+    mut root_query: Query<&mut Style, With<KeypadRoot>>,
+) {
+    let shift = input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+    if !(shift && input.just_pressed(KeyCode::F12)) {
+        return;
+    }
+    panel.visible = !panel.visible;
+    for mut style in root_query.iter_mut() {
+        style.display = if panel.visible {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
+
+// Names the first keyboard key bound to each CHIP-8 key. The key map is only
+// inserted once the ROM is loaded, so this waits for it.
+fn label_keys(keymap: Option<Res<KeyMap>>, mut label_query: Query<(&mut Text, &KeyLabel)>) {
+    let Some(keymap) = keymap.filter(|keymap| keymap.is_changed()) else {
+        return;
+    };
+    for (mut text, KeyLabel(key)) in label_query.iter_mut() {
+        text.sections[0].value = keymap
+            .keys(*key)
+            .first()
+            .map_or_else(String::new, |code| key_label(*code));
+    }
+}
+
+fn key_label(code: KeyCode) -> String {
+    let name = format!("{:?}", code);
+    match name.strip_prefix("Key") {
+        Some(digit) => digit.to_string(),
+        None => name.replace("Numpad", "Num"),
+    }
+}
+
+// Holds down the keys being clicked or touched, on top of the keyboard and
+// gamepads. Runs between handle_input and cycle.
+pub fn press_keypad(
+    panel: Res<KeypadPanel>,
+    button_query: Query<(&Interaction, &KeypadButton)>,
+    mut cpu: ResMut<Cpu>,
+    movie: Res<MovieState>,
+) {
+    if !panel.visible || movie.is_replaying() {
+        return;
+    }
+    for (interaction, KeypadButton(key)) in button_query.iter() {
+        if *interaction == Interaction::Clicked {
+            cpu.keypad[*key] = 1;
+        }
+    }
+}
+
+// Lights every key the machine sees as down, whatever pressed it, in the
+// palette's lit color with the digit in its unlit one.
+fn light_keys(
+    cpu: Res<Cpu>,
+    palettes: Res<Palettes>,
+    mut button_query: Query<(&Interaction, &KeypadButton, &mut BackgroundColor)>,
+    mut digit_query: Query<(&mut Text, &KeyDigit)>,
+) {
+    let [unlit, lit, ..] = palettes.current().colors;
+    for (mut text, KeyDigit(key)) in digit_query.iter_mut() {
+        let color = if cpu.keypad[*key] != 0 {
+            unlit
+        } else {
+            Color::WHITE
+        };
+        if text.sections[0].style.color != color {
+            text.sections[0].style.color = color;
+        }
+    }
+    for (interaction, KeypadButton(key), mut background) in button_query.iter_mut() {
+        let color = if cpu.keypad[*key] != 0 {
+            lit
+        } else if *interaction == Interaction::Hovered {
+            HOVER_COLOR
+        } else {
+            KEY_COLOR
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
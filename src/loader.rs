@@ -1,38 +1,96 @@
+use crate::args::Args;
+use crate::config::Config;
+use crate::cpu::CpuClock;
+use crate::romdb::RomInfo;
 use crate::Cpu;
-use bevy::prelude::ResMut;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use chip8_core::{disassemble, sha1, Chip8, Clock, DEFAULT_CPU_HZ};
 
-use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::process;
 
-// TODO check if writing to cpu.memory works.
+// SHA-1 of the running ROM, so save states can tell which ROM they belong to.
+#[derive(Resource, Default)]
+pub struct RomHash(pub [u8; 20]);
 
-pub fn load_rom(mut cpu: ResMut<Cpu>) {
-    // Read the command-line argument for the file name
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        eprintln!("Please provide the ROM path !");
+impl RomHash {
+    // Lowercase hex, as used to name per-ROM sections in the config file and
+    // in the ROM database.
+    pub fn hex(&self) -> String {
+        hex(&self.0)
     }
-    let file_name = &args[1];
+}
+
+fn hex(hash: &[u8; 20]) -> String {
+    hash.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
 
-    // Open the file
-    let mut file = File::open(file_name).unwrap();
+// Settings the command line leaves open come from the ROM database, then
+// from the defaults.
+pub fn load_rom(
+    mut cpu: ResMut<Cpu>,
+    mut clock: ResMut<CpuClock>,
+    mut rom_hash: ResMut<RomHash>,
+    mut rom_info: ResMut<RomInfo>,
+    args: Res<Args>,
+    config: Res<Config>,
+) {
+    let rom = read_rom(&args);
+    rom_hash.0 = sha1(&rom);
+    *rom_info = RomInfo::lookup(&rom_hash.hex(), &config);
+
+    let platform = args.platform.or(rom_info.platform).unwrap_or_default();
+    let quirks = args.quirks.or(rom_info.quirks);
+    let cpu_hz = args.cpu_hz.or(rom_info.cpu_hz).unwrap_or(DEFAULT_CPU_HZ);
+    clock.0 = Clock::new(cpu_hz);
+
+    cpu.0 = Chip8::with_platform(platform);
+    cpu.quirks = quirks.unwrap_or_else(|| platform.default_quirks());
+    cpu.random = args.random.source(args.seed);
+    if let Err(err) = cpu.load_rom(&rom) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
 
-    // Read the file byte by byte
-    let mut buffer = [0u8; 1];
+// Shows the title and authors of a ROM the database knows.
+pub fn show_title(
+    rom_info: Res<RomInfo>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if let (Some(description), Ok(mut window)) =
+        (rom_info.describe(), window_query.get_single_mut())
+    {
+        println!("Running {}", description);
+        window.title = window_title(&rom_info);
+    }
+}
 
-    let mut index: usize = cpu.pc as usize;
+// The window title while the program runs normally.
+pub fn window_title(rom_info: &RomInfo) -> String {
+    match rom_info.describe() {
+        Some(description) => format!("CHIP-8 - {}", description),
+        None => "CHIP-8".to_string(),
+    }
+}
 
-    loop {
-        match file.read_exact(&mut buffer) {
-            Ok(_) => {
-                // Print the byte code
-                // print!("{:02X} ", buffer[0]);
+// Prints the listing for --disasm.
+pub fn print_disassembly(args: &Args, config: &Config) {
+    let rom = read_rom(args);
+    let platform = args
+        .platform
+        .or_else(|| RomInfo::lookup(&hex(&sha1(&rom)), config).platform)
+        .unwrap_or_default();
+    print!("{}", disassemble(&rom, platform));
+}
 
-                cpu.memory[index] = buffer[0];
-                index += 1;
-            }
-            Err(_) => break, // Reached end of file or encountered an error
+pub fn read_rom(args: &Args) -> Vec<u8> {
+    match fs::read(&args.rom_path) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Could not read {}: {}", args.rom_path, err);
+            process::exit(1);
         }
     }
 }
@@ -1,62 +1,108 @@
 use bevy::prelude::*;
-use bevy::window::PrimaryWindow;
+use bevy::window::WindowMode;
 
-use cpu::{Cpu, cycle};
-use font::load_font;
+use args::Args;
+use audio::BeeperPlugin;
+use chip8_core::{Clock, DEFAULT_CPU_HZ};
+use config::Config;
+use cpu::{cycle, Cpu, CpuClock, Halted};
+use debugger::DebuggerPlugin;
 use graphics::*;
-use keymap::handle_input;
-use loader::load_rom;
-use timers::tick_timer;
+use keymap::{handle_input, load_keymap, log_gamepads};
+use keypad::{press_keypad, KeypadPlugin, KEYPAD_WIDTH};
+use loader::{load_rom, print_disassembly, show_title, RomHash};
+use movie::{record_movie, replay_movie, save_movie, start_movie, MovieState};
+use palette::{apply_rom_palette, palette_hotkeys, Palettes};
+use rewind::{record_rewind, rewind, RewindBuffer, Rewinding};
+use romdb::RomInfo;
+use savestate::{save_state_hotkeys, SaveSlot};
+use timers::{tick_timer, TimerClock};
 
+mod args;
+mod audio;
+mod config;
 mod cpu;
-mod font;
+mod debugger;
 mod graphics;
 mod keymap;
+mod keypad;
 mod loader;
+mod movie;
+mod palette;
+mod rewind;
+mod romdb;
+mod savestate;
 mod timers;
+mod ui;
 
 const WINDOW_SIZE: (f32, f32) = (640.0, 320.0);
 
 fn main() {
+    let args = Args::parse();
+    let config = Config::load(&args);
+    if args.disasm {
+        print_disassembly(&args, &config);
+        return;
+    }
+    let panel_width = if args.keypad { KEYPAD_WIDTH } else { 0.0 };
+
     App::new()
         .add_plugins(DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
-                resolution: WINDOW_SIZE.into(),
+                title: "CHIP-8".to_string(),
+                resolution: (WINDOW_SIZE.0 + panel_width, WINDOW_SIZE.1).into(),
+                mode: if args.fullscreen {
+                    WindowMode::BorderlessFullscreen
+                } else {
+                    WindowMode::Windowed
+                },
                 ..default()
             }),
             ..default()
         }))
+        .add_plugin(BeeperPlugin)
+        .add_plugin(DebuggerPlugin)
         .insert_resource(ClearColor(Color::BLACK))
         .init_resource::<Cpu>()
+        .init_resource::<Halted>()
+        .init_resource::<TimerClock>()
+        .init_resource::<RomHash>()
+        .init_resource::<RomInfo>()
+        .init_resource::<SaveSlot>()
+        .init_resource::<RewindBuffer>()
+        .init_resource::<Rewinding>()
+        .init_resource::<MovieState>()
+        // load_rom sets the real speed once the ROM is known.
+        .insert_resource(CpuClock(Clock::new(DEFAULT_CPU_HZ)))
+        .insert_resource(args)
+        .insert_resource(config)
+        .init_resource::<Palettes>()
+        .init_resource::<Screen>()
+        .init_resource::<Scaling>()
+        .add_plugin(KeypadPlugin)
         .add_startup_system(spawn_camera)
+        .add_startup_system(spawn_screen)
         .add_startup_system(load_rom)
-        .add_startup_system(load_font)
-        .add_system(cycle)
-        .add_system(remove_pixel)
-        .add_system(draw_pixel.after(remove_pixel))
+        .add_startup_system(start_movie.after(load_rom))
+        .add_startup_system(load_keymap.after(load_rom))
+        .add_startup_system(show_title.after(load_rom))
+        .add_startup_system(apply_rom_palette.after(load_rom))
+        .add_system(rewind.before(cycle).before(tick_timer))
+        // Input, then instructions, then timers, in the same order every
+        // frame so a movie replays exactly.
+        .add_system(cycle.after(handle_input).after(press_keypad))
+        .add_system(press_keypad.after(handle_input))
+        .add_system(replay_movie.after(handle_input).before(tick_timer))
+        .add_system(palette_hotkeys)
+        .add_system(draw_screen.after(palette_hotkeys))
+        .add_system(scaling_hotkeys)
+        .add_system(fit_screen.after(scaling_hotkeys))
         .add_system(handle_input)
-        .add_system(tick_timer)
-        .add_system(get_input)
+        .add_system(log_gamepads)
+        .add_system(tick_timer.after(cycle))
+        .add_system(save_state_hotkeys)
+        .add_system(record_rewind.after(cycle).after(tick_timer))
+        .add_system(record_movie.after(tick_timer))
+        .add_system(save_movie.in_base_set(CoreSet::Last))
         .run();
 }
-
-fn spawn_camera(mut commands: Commands, window_query: Query<&Window, With<PrimaryWindow>>) {
-    let window = window_query.get_single().unwrap();
-
-    commands.spawn(Camera2dBundle {
-        transform: Transform::from_xyz(window.width() / 2.0, window.height() / 2.0, 0.0),
-        ..default()
-    });
-}
-
-// DEBUG
-fn get_input(input: Res<Input<KeyCode>>, mut cpu: ResMut<Cpu>) {
-    if input.just_pressed(KeyCode::Up) {
-        if cpu.display[10][20] == 0 {
-            cpu.display[10][20] = 1;
-        } else {
-            cpu.display[10][20] = 0;
-        }
-        cpu.redraw = true;
-    }
-}
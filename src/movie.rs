@@ -0,0 +1,147 @@
+use crate::args::Args;
+use crate::cpu::Halted;
+use crate::debugger::Debugger;
+use crate::loader::{read_rom, RomHash};
+use crate::Cpu;
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use chip8_core::{Frame, Movie};
+
+use std::fs;
+use std::process;
+
+// Whether this run is being recorded to a movie file (--record), played back
+// from one (--replay) or neither.
+#[derive(Resource, Default)]
+pub enum MovieState {
+    #[default]
+    Off,
+    Recording {
+        path: String,
+        movie: Movie,
+        // What has happened so far this frame.
+        frame: Frame,
+    },
+    Replaying {
+        movie: Movie,
+        next: usize,
+    },
+}
+
+impl MovieState {
+    pub fn is_active(&self) -> bool {
+        !matches!(self, MovieState::Off)
+    }
+
+    // While replaying, the movie drives the keypad, the CPU and the timers.
+    pub fn is_replaying(&self) -> bool {
+        matches!(self, MovieState::Replaying { .. })
+    }
+
+    pub fn count_step(&mut self) {
+        if let MovieState::Recording { frame, .. } = self {
+            frame.steps += 1;
+        }
+    }
+
+    pub fn count_ticks(&mut self, ticks: u32) {
+        if let MovieState::Recording { frame, .. } = self {
+            frame.ticks = frame.ticks.saturating_add(ticks.min(u8::MAX as u32) as u8);
+        }
+    }
+}
+
+// Runs after load_rom. A replay swaps in the machine the movie was recorded
+// on; a recording notes how load_rom seeded Cxkk's random source.
+pub fn start_movie(
+    mut cpu: ResMut<Cpu>,
+    mut state: ResMut<MovieState>,
+    rom_hash: Res<RomHash>,
+    args: Res<Args>,
+) {
+    if let Some(path) = &args.replay {
+        let movie = fs::read(path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| Movie::from_bytes(&data, &rom_hash.0).map_err(|err| err.to_string()));
+        let movie = match movie {
+            Ok(movie) => movie,
+            Err(err) => {
+                eprintln!("Could not replay {}: {}", path, err);
+                process::exit(1);
+            }
+        };
+
+        cpu.0 = movie.machine();
+        if let Err(err) = cpu.load_rom(&read_rom(&args)) {
+            eprintln!("{}", err);
+            process::exit(1);
+        }
+        println!("Replaying {} frames from {}", movie.frames.len(), path);
+        *state = MovieState::Replaying { movie, next: 0 };
+    } else if let Some(path) = &args.record {
+        println!("Recording to {}", path);
+        *state = MovieState::Recording {
+            path: path.clone(),
+            movie: Movie::new(&cpu, rom_hash.0, args.random, args.seed),
+            frame: Frame::default(),
+        };
+    }
+}
+
+// Runs after cycle and tick_timer, once everything this frame did has been
+// counted.
+pub fn record_movie(cpu: Res<Cpu>, mut state: ResMut<MovieState>) {
+    let MovieState::Recording { movie, frame, .. } = &mut *state else {
+        return;
+    };
+    // Paused frames change nothing and are left out.
+    if frame.steps == 0 && frame.ticks == 0 {
+        return;
+    }
+    frame.keys = Frame::keys_of(&cpu);
+    movie.frames.push(*frame);
+    *frame = Frame::default();
+}
+
+// Plays one recorded frame per rendered frame. The debugger can pause a
+// replay between frames; breakpoints are not checked.
+pub fn replay_movie(
+    mut cpu: ResMut<Cpu>,
+    mut halted: ResMut<Halted>,
+    mut state: ResMut<MovieState>,
+    mut debugger: ResMut<Debugger>,
+) {
+    let MovieState::Replaying { movie, next } = &mut *state else {
+        return;
+    };
+    if debugger.is_paused() || halted.0.is_some() {
+        return;
+    }
+
+    match movie.frames.get(*next) {
+        Some(frame) => {
+            *next += 1;
+            if let Err(err) = frame.play(&mut cpu) {
+                eprintln!("Halted: {}", err);
+                halted.0 = Some(err);
+                debugger.pause();
+            }
+        }
+        None => {
+            println!("Replay finished, the keyboard is live again");
+            *state = MovieState::Off;
+        }
+    }
+}
+
+pub fn save_movie(mut exit: EventReader<AppExit>, state: Res<MovieState>) {
+    if exit.iter().next().is_none() {
+        return;
+    }
+    if let MovieState::Recording { path, movie, .. } = &*state {
+        match fs::write(path, movie.to_bytes()) {
+            Ok(()) => println!("Saved {} frames to {}", movie.frames.len(), path),
+            Err(err) => eprintln!("Could not write {}: {}", path, err),
+        }
+    }
+}
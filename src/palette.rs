@@ -0,0 +1,168 @@
+use crate::args::Args;
+use crate::config::Config;
+use crate::romdb::RomInfo;
+use bevy::prelude::*;
+
+use std::process;
+
+// Built-in palettes as unlit, plane 1, plane 2 and both planes. Only XO-CHIP
+// lights more than the first plane.
+const BUILTIN: [(&str, [&str; 4]); 5] = [
+    ("classic", ["#000000", "#FFFFFF", "#FF6600", "#662200"]),
+    ("green", ["#041004", "#33FF33", "#1A801A", "#B3FFB3"]),
+    ("amber", ["#140C00", "#FFB000", "#805800", "#FFD980"]),
+    ("gameboy", ["#9BBC0F", "#0F380F", "#8BAC0F", "#306230"]),
+    ("contrast", ["#000000", "#FFFFFF", "#FFFF00", "#00FFFF"]),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    pub name: String,
+    // Indexed by the bitplanes lit at a pixel.
+    pub colors: [Color; 4],
+}
+
+impl Palette {
+    // Parses "#000000, #FFFFFF" or four colors for XO-CHIP. With two colors,
+    // the second plane gets shades between them.
+    pub fn parse(name: &str, text: &str) -> Result<Palette, String> {
+        let colors = text
+            .split(',')
+            .map(|hex| {
+                Color::hex(hex.trim()).map_err(|_| format!("invalid color '{}'", hex.trim()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let colors = match colors[..] {
+            [off, on] => [off, on, mix(off, on, 0.5), mix(off, on, 0.25)],
+            [off, one, two, both] => [off, one, two, both],
+            _ => return Err(format!("palette '{}' needs 2 or 4 colors", name)),
+        };
+        Ok(Palette {
+            name: name.to_string(),
+            colors,
+        })
+    }
+}
+
+fn mix(from: Color, to: Color, amount: f32) -> Color {
+    let [r1, g1, b1, _] = from.as_rgba_f32();
+    let [r2, g2, b2, _] = to.as_rgba_f32();
+    Color::rgb(
+        r1 + (r2 - r1) * amount,
+        g1 + (g2 - g1) * amount,
+        b1 + (b2 - b1) * amount,
+    )
+}
+
+// The palettes F3 cycles through: the built-in ones followed by those in the
+// [palettes] section of the config file, which may also replace a built-in one.
+#[derive(Resource)]
+pub struct Palettes {
+    list: Vec<Palette>,
+    current: usize,
+}
+
+impl Palettes {
+    pub fn current(&self) -> &Palette {
+        &self.list[self.current]
+    }
+
+    // Switches to the palette called `name`, or to the colors `name` lists.
+    pub fn select(&mut self, name: &str) -> Result<(), String> {
+        if let Some(index) = self.list.iter().position(|palette| palette.name == name) {
+            self.current = index;
+            return Ok(());
+        }
+        if !name.contains(',') {
+            let names: Vec<&str> = self
+                .list
+                .iter()
+                .map(|palette| palette.name.as_str())
+                .collect();
+            return Err(format!(
+                "unknown palette '{}', expected one of: {} or a list of hex colors",
+                name,
+                names.join(", ")
+            ));
+        }
+        self.list.push(Palette::parse("custom", name)?);
+        self.current = self.list.len() - 1;
+        Ok(())
+    }
+
+    fn cycle(&mut self, forward: bool) {
+        let len = self.list.len();
+        self.current = if forward {
+            (self.current + 1) % len
+        } else {
+            (self.current + len - 1) % len
+        };
+    }
+}
+
+impl FromWorld for Palettes {
+    fn from_world(world: &mut World) -> Self {
+        let mut list: Vec<Palette> = BUILTIN
+            .iter()
+            .map(|(name, colors)| Palette {
+                name: name.to_string(),
+                colors: colors.map(|hex| Color::hex(hex).expect("built-in colors are valid")),
+            })
+            .collect();
+
+        let config = world.resource::<Config>();
+        for (name, value) in &config.palettes {
+            // Either "#000000, #FFFFFF" or ["#000000", "#FFFFFF"].
+            match Palette::parse(name, &value.as_list().join(",")) {
+                Ok(palette) => {
+                    list.retain(|other| other.name != palette.name);
+                    list.push(palette);
+                }
+                Err(err) => exit_with_error(&err),
+            }
+        }
+
+        let mut palettes = Palettes { list, current: 0 };
+        let start = world
+            .resource::<Args>()
+            .palette
+            .clone()
+            .or_else(|| config.palette.clone());
+        if let Some(start) = start {
+            if let Err(err) = palettes.select(&start) {
+                exit_with_error(&err);
+            }
+        }
+        palettes
+    }
+}
+
+// Switches to the colors the ROM database gives for the ROM, unless --palette
+// picked some. They are kept in the F3 cycle as "rom".
+pub fn apply_rom_palette(args: Res<Args>, rom_info: Res<RomInfo>, mut palettes: ResMut<Palettes>) {
+    let Some(colors) = rom_info.colors.as_ref().filter(|_| args.palette.is_none()) else {
+        return;
+    };
+    match Palette::parse("rom", colors) {
+        Ok(palette) => {
+            palettes.list.push(palette);
+            palettes.current = palettes.list.len() - 1;
+        }
+        Err(err) => eprintln!("Ignoring the ROM database colors: {}", err),
+    }
+}
+
+fn exit_with_error(err: &str) -> ! {
+    eprintln!("{}", err);
+    process::exit(1);
+}
+
+// F3 switches to the next palette, Shift+F3 to the previous one.
+pub fn palette_hotkeys(input: Res<Input<KeyCode>>, mut palettes: ResMut<Palettes>) {
+    if input.just_pressed(KeyCode::F3) {
+        let shift = input.any_pressed([KeyCode::LShift, KeyCode::RShift]);
+        palettes.cycle(!shift);
+        println!("Palette {}", palettes.current().name);
+    }
+}
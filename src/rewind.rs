@@ -0,0 +1,74 @@
+use crate::cpu::Halted;
+use crate::debugger::Debugger;
+use crate::loader::window_title;
+use crate::movie::MovieState;
+use crate::romdb::RomInfo;
+use crate::Cpu;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use chip8_core::Rewind;
+
+// Held to run the emulation backwards one recorded frame at a time.
+const REWIND_KEY: KeyCode = KeyCode::Back;
+
+// The last 30 seconds of machine states, one per frame.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct RewindBuffer(pub Rewind);
+
+// Set while the rewind key is held. The CPU and the timers stand still and
+// the screen plays back the recorded states instead.
+#[derive(Resource, Default)]
+pub struct Rewinding(pub bool);
+
+// Stepping back out of a fault or an exit also resumes the debugger the
+// fault paused and puts the window title back.
+#[allow(clippy::too_many_arguments)]
+pub fn rewind(
+    input: Res<Input<KeyCode>>,
+    mut cpu: ResMut<Cpu>,
+    mut halted: ResMut<Halted>,
+    mut buffer: ResMut<RewindBuffer>,
+    mut rewinding: ResMut<Rewinding>,
+    mut debugger: ResMut<Debugger>,
+    movie: Res<MovieState>,
+    rom_info: Res<RomInfo>,
+    mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    // Going back in time would leave a movie out of step with the machine.
+    rewinding.0 = input.pressed(REWIND_KEY) && !debugger.is_prompting() && !movie.is_active();
+    if !rewinding.0 {
+        return;
+    }
+
+    // Stays on the oldest state once the buffer runs out.
+    let stopped = halted.0.is_some() || cpu.exited;
+    match buffer.pop(&mut cpu) {
+        Ok(true) if stopped => {
+            if halted.0.take().is_some() {
+                debugger.resume();
+            }
+            if let Ok(mut window) = window_query.get_single_mut() {
+                window.title = window_title(&rom_info);
+            }
+        }
+        Ok(_) => (),
+        Err(err) => {
+            eprintln!("Could not rewind: {}", err);
+            buffer.clear();
+        }
+    }
+}
+
+pub fn record_rewind(
+    cpu: Res<Cpu>,
+    halted: Res<Halted>,
+    mut buffer: ResMut<RewindBuffer>,
+    rewinding: Res<Rewinding>,
+    debugger: Res<Debugger>,
+) {
+    // Nothing changes while stopped, so there is nothing worth recording.
+    if rewinding.0 || debugger.is_paused() || halted.0.is_some() || cpu.exited {
+        return;
+    }
+    buffer.push(&cpu);
+}
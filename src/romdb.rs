@@ -0,0 +1,272 @@
+use crate::config::Config;
+use bevy::prelude::Resource;
+use chip8_core::{IndexIncrement, Platform, Quirks};
+use serde::Deserialize;
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::process;
+
+// The copy of the database compiled in; see assets/database/README.md. It
+// is empty until a release's files are copied there.
+const BUNDLED_PROGRAMS: &str = include_str!("../assets/database/programs.json");
+const BUNDLED_HASHES: &str = include_str!("../assets/database/sha1-hashes.json");
+
+// The database runs everything at 60 frames per second and counts
+// instructions per frame.
+const FRAMES_PER_SECOND: u32 = 60;
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    #[serde(default)]
+    quirky_platforms: HashMap<String, QuirkOverrides>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+// Quirks a ROM needs that differ from its platform's usual ones. Each is
+// named for the behaviour that sets it apart from the COSMAC VIP.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct QuirkOverrides {
+    shift: Option<bool>,
+    memory_increment_by_x: Option<bool>,
+    memory_leave_i_unchanged: Option<bool>,
+    wrap: Option<bool>,
+    jump: Option<bool>,
+    logic: Option<bool>,
+}
+
+// What the database knows about the running ROM. Everything is optional, and
+// command-line options still win over it.
+#[derive(Resource, Default)]
+pub struct RomInfo {
+    pub title: Option<String>,
+    pub authors: Vec<String>,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub cpu_hz: Option<u32>,
+    // Two or four hex colors, as --palette takes them.
+    pub colors: Option<String>,
+    // CHIP-8 keys for "up", "down", "left", "right", "a" and "b".
+    pub keys: Vec<(String, usize)>,
+}
+
+impl RomInfo {
+    // Looks up the ROM with SHA-1 `hash` (lowercase hex) in the bundled
+    // database, or in the one the config file's `database` entry points at.
+    // Unknown ROMs get an empty RomInfo.
+    pub fn lookup(hash: &str, config: &Config) -> RomInfo {
+        let database = match &config.database {
+            Some(dir) => RomDatabase::read(Path::new(dir)),
+            None => RomDatabase::bundled(),
+        };
+        database.lookup(hash)
+    }
+
+    // "Title by Author" for the window title.
+    pub fn describe(&self) -> Option<String> {
+        let title = self.title.as_ref()?;
+        if self.authors.is_empty() {
+            Some(title.clone())
+        } else {
+            Some(format!("{} by {}", title, self.authors.join(", ")))
+        }
+    }
+}
+
+// programs.json and sha1-hashes.json from the database/ folder of a
+// chip-8-database checkout. The hashes index into the programs.
+struct RomDatabase {
+    programs: Vec<Program>,
+    hashes: HashMap<String, usize>,
+}
+
+impl RomDatabase {
+    fn read(dir: &Path) -> RomDatabase {
+        let read = |name: &str| {
+            let path = dir.join(name);
+            fs::read_to_string(&path).unwrap_or_else(|err| {
+                eprintln!("Could not read {}: {}", path.display(), err);
+                process::exit(1);
+            })
+        };
+        RomDatabase::parse_or_exit(&read("programs.json"), &read("sha1-hashes.json"))
+    }
+
+    fn bundled() -> RomDatabase {
+        RomDatabase::parse_or_exit(BUNDLED_PROGRAMS, BUNDLED_HASHES)
+    }
+
+    fn parse_or_exit(programs: &str, hashes: &str) -> RomDatabase {
+        RomDatabase::parse(programs, hashes).unwrap_or_else(|err| {
+            eprintln!("Could not parse the ROM database: {}", err);
+            process::exit(1);
+        })
+    }
+
+    fn parse(programs: &str, hashes: &str) -> Result<RomDatabase, serde_json::Error> {
+        Ok(RomDatabase {
+            programs: serde_json::from_str(programs)?,
+            hashes: serde_json::from_str(hashes)?,
+        })
+    }
+
+    fn lookup(&self, hash: &str) -> RomInfo {
+        let Some(program) = self
+            .hashes
+            .get(hash)
+            .and_then(|&index| self.programs.get(index))
+        else {
+            return RomInfo::default();
+        };
+        let Some(rom) = program.roms.get(hash) else {
+            return RomInfo::default();
+        };
+
+        let platform = rom
+            .platforms
+            .iter()
+            .find_map(|id| platform_quirks(id).map(|found| (id, found)));
+        let quirks = platform.map(|(id, (_, quirks))| match rom.quirky_platforms.get(id) {
+            Some(overrides) => overrides.apply(quirks),
+            None => quirks,
+        });
+        let colors = rom
+            .colors
+            .as_ref()
+            .filter(|colors| matches!(colors.pixels.len(), 2 | 4))
+            .map(|colors| colors.pixels.join(","));
+        let mut keys: Vec<(String, usize)> = rom
+            .keys
+            .iter()
+            .filter(|(_, &key)| key < 16)
+            .map(|(action, &key)| (action.clone(), key as usize))
+            .collect();
+        keys.sort();
+
+        RomInfo {
+            title: Some(program.title.clone()),
+            authors: program.authors.clone(),
+            platform: platform.map(|(_, (platform, _))| platform),
+            quirks,
+            cpu_hz: rom.tickrate.map(|rate| rate.max(1) * FRAMES_PER_SECOND),
+            colors,
+            keys,
+        }
+    }
+}
+
+// The database's platform ids this emulator can run, with the quirks of the
+// interpreter each one stands for.
+fn platform_quirks(id: &str) -> Option<(Platform, Quirks)> {
+    match id {
+        "originalChip8" | "hybridVIP" => Some((Platform::Chip8, Quirks::COSMAC_VIP)),
+        "modernChip8" => Some((Platform::Chip8, Quirks::MODERN)),
+        "chip48" => Some((Platform::Chip8, Quirks::CHIP_48)),
+        "superchip1" | "superchip" => Some((Platform::SuperChip, Quirks::SUPER_CHIP)),
+        "xochip" => Some((Platform::XoChip, Quirks::XO_CHIP)),
+        _ => None,
+    }
+}
+
+impl QuirkOverrides {
+    fn apply(&self, mut quirks: Quirks) -> Quirks {
+        if let Some(shift) = self.shift {
+            quirks.shift_uses_vy = !shift;
+        }
+        if self.memory_leave_i_unchanged == Some(true) {
+            quirks.index_increment = IndexIncrement::Unchanged;
+        } else if self.memory_increment_by_x == Some(true) {
+            quirks.index_increment = IndexIncrement::ByX;
+        } else if self.memory_leave_i_unchanged.is_some() || self.memory_increment_by_x.is_some() {
+            quirks.index_increment = IndexIncrement::ByXPlusOne;
+        }
+        if let Some(wrap) = self.wrap {
+            quirks.clip_sprites = !wrap;
+        }
+        if let Some(jump) = self.jump {
+            quirks.jump_uses_vx = jump;
+        }
+        if let Some(logic) = self.logic {
+            quirks.logic_resets_vf = logic;
+        }
+        quirks
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAMS: &str = r#"[
+        {
+            "title": "Pong",
+            "authors": ["Paul Vervalin"],
+            "roms": {
+                "aaaa": { "platforms": ["originalChip8"], "tickrate": 15 }
+            }
+        },
+        {
+            "title": "Blinky",
+            "roms": {
+                "bbbb": {
+                    "platforms": ["superchip"],
+                    "keys": { "left": 7, "a": 6 },
+                    "quirkyPlatforms": { "superchip": { "shift": false, "jump": false } }
+                }
+            }
+        }
+    ]"#;
+    const HASHES: &str = r#"{ "aaaa": 0, "bbbb": 1 }"#;
+
+    #[test]
+    fn finds_programs_by_hash() {
+        let database = RomDatabase::parse(PROGRAMS, HASHES).unwrap();
+
+        let pong = database.lookup("aaaa");
+        assert_eq!(pong.describe().as_deref(), Some("Pong by Paul Vervalin"));
+        assert_eq!(pong.platform, Some(Platform::Chip8));
+        assert_eq!(pong.quirks, Some(Quirks::COSMAC_VIP));
+        assert_eq!(pong.cpu_hz, Some(900));
+
+        let blinky = database.lookup("bbbb");
+        let mut quirks = Quirks::SUPER_CHIP;
+        quirks.shift_uses_vy = true;
+        quirks.jump_uses_vx = false;
+        assert_eq!(blinky.platform, Some(Platform::SuperChip));
+        assert_eq!(blinky.quirks, Some(quirks));
+        assert_eq!(blinky.keys, [("a".to_string(), 6), ("left".to_string(), 7)]);
+
+        assert!(database.lookup("cccc").title.is_none());
+    }
+
+    #[test]
+    fn bundled_copy_finds_its_own_hashes() {
+        let database = RomDatabase::parse(BUNDLED_PROGRAMS, BUNDLED_HASHES).unwrap();
+        for hash in database.hashes.keys() {
+            let info = database.lookup(hash);
+            assert!(info.title.is_some(), "{} is not in its program", hash);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::args::Args;
+use crate::cpu::Halted;
+use crate::loader::RomHash;
+use crate::movie::MovieState;
+use crate::Cpu;
+use bevy::prelude::*;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const SLOTS: u8 = 10;
+
+// Slot F2 saves to and F4 loads from. F6/F7 pick the previous/next one.
+#[derive(Resource, Default)]
+pub struct SaveSlot(pub u8);
+
+// Slot n of game.ch8 is stored next to it as game.state<n>.
+fn slot_path(rom_path: &str, slot: u8) -> PathBuf {
+    Path::new(rom_path).with_extension(format!("state{}", slot))
+}
+
+pub fn save_state_hotkeys(
+    input: Res<Input<KeyCode>>,
+    mut cpu: ResMut<Cpu>,
+    mut halted: ResMut<Halted>,
+    mut slot: ResMut<SaveSlot>,
+    rom_hash: Res<RomHash>,
+    movie: Res<MovieState>,
+    args: Res<Args>,
+) {
+    if input.just_pressed(KeyCode::F6) {
+        slot.0 = (slot.0 + SLOTS - 1) % SLOTS;
+        println!("Save slot {}", slot.0);
+    }
+    if input.just_pressed(KeyCode::F7) {
+        slot.0 = (slot.0 + 1) % SLOTS;
+        println!("Save slot {}", slot.0);
+    }
+
+    let path = slot_path(&args.rom_path, slot.0);
+    if input.just_pressed(KeyCode::F2) {
+        match fs::write(&path, cpu.save_state(&rom_hash.0)) {
+            Ok(()) => println!("Saved slot {} to {}", slot.0, path.display()),
+            Err(err) => eprintln!("Could not write {}: {}", path.display(), err),
+        }
+    }
+    if input.just_pressed(KeyCode::F4) && movie.is_active() {
+        eprintln!("Cannot load a state while a movie is recording or playing");
+    } else if input.just_pressed(KeyCode::F4) {
+        let result = fs::read(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|data| {
+                cpu.load_state(&data, &rom_hash.0)
+                    .map_err(|err| err.to_string())
+            });
+        match result {
+            Ok(()) => {
+                halted.0 = None;
+                println!("Loaded slot {} from {}", slot.0, path.display());
+            }
+            Err(err) => eprintln!("Could not load {}: {}", path.display(), err),
+        }
+    }
+}
@@ -1,28 +1,25 @@
+use super::debugger::Debugger;
+use super::movie::MovieState;
+use super::rewind::Rewinding;
 use super::Cpu;
-use bevy::prelude::{Res, ResMut, Time};
+use bevy::prelude::*;
+use chip8_core::Timers;
 
-// Decrement at 60Hz (decrement by 1 every 16.666 milliseconds)
-const DECREMENT_RATE: u8 = 16;
+// Counts the delay and sound timers down at 60Hz regardless of frame rate.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct TimerClock(pub Timers);
 
-pub fn tick_timer(mut cpu: ResMut<Cpu>, time: Res<Time>) {
-    // time.delta().as_millis()
-    let decrement: u8 = (time.delta().as_millis() as u8) / DECREMENT_RATE;
-    if cpu.delay_timer > 0 {
-        // Decrement timers at 60Hz
-        if cpu.delay_timer > decrement {
-            cpu.delay_timer -= decrement;
-        } else {
-            cpu.delay_timer = 0
-        }
-    }
-    if cpu.sound_timer > 0 {
-        // TODO: Play Sound
-
-        // Decrement timers at 60Hz
-        if cpu.sound_timer > decrement {
-            cpu.sound_timer -= decrement;
-        } else {
-            cpu.sound_timer = 0
-        }
+pub fn tick_timer(
+    mut cpu: ResMut<Cpu>,
+    mut timers: ResMut<TimerClock>,
+    debugger: Res<Debugger>,
+    rewinding: Res<Rewinding>,
+    mut movie: ResMut<MovieState>,
+    time: Res<Time>,
+) {
+    if debugger.is_paused() || rewinding.0 || movie.is_replaying() {
+        return;
     }
+    let ticks = timers.update(&mut cpu, time.delta());
+    movie.count_ticks(ticks);
 }
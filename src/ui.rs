@@ -0,0 +1,14 @@
+use bevy::prelude::*;
+
+// Monospace font for the overlays. Bevy has no built-in UI font, so it is
+// compiled into the binary instead of being loaded from the asset folder.
+#[derive(Resource, Deref)]
+pub struct UiFont(pub Handle<Font>);
+
+impl FromWorld for UiFont {
+    fn from_world(world: &mut World) -> Self {
+        let bytes = include_bytes!("../assets/fonts/DejaVuSansMono.ttf");
+        let font = Font::try_from_bytes(bytes.to_vec()).expect("embedded font is valid");
+        UiFont(world.resource_mut::<Assets<Font>>().add(font))
+    }
+}